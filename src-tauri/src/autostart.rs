@@ -0,0 +1,177 @@
+use std::io;
+
+use crate::config::WanipopConfig;
+
+pub trait AutostartBackend {
+    fn is_enabled(&self) -> io::Result<bool>;
+    fn enable(&self) -> io::Result<()>;
+    fn disable(&self) -> io::Result<()>;
+}
+
+#[cfg(target_os = "linux")]
+pub fn backend() -> Box<dyn AutostartBackend> {
+    Box::new(xdg::XdgAutostart::new())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn backend() -> Box<dyn AutostartBackend> {
+    Box::new(Unsupported)
+}
+
+pub fn set_enabled(enabled: bool) -> io::Result<()> {
+    let backend = backend();
+    if enabled {
+        backend.enable()
+    } else {
+        backend.disable()
+    }
+}
+
+// The autostart entry can be removed behind our back, so what's on disk wins over the config
+pub fn sync_config(config: &mut WanipopConfig) -> io::Result<()> {
+    let backend = backend();
+    let enabled = backend.is_enabled()?;
+
+    if enabled {
+        // Rewrite the entry in case the executable moved since it was created
+        backend.enable()?;
+    }
+
+    if enabled != config.autostart {
+        println!("Autostart is {} on disk, updating config", enabled);
+        config.set_autostart(enabled)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Unsupported;
+
+#[cfg(not(target_os = "linux"))]
+impl AutostartBackend for Unsupported {
+    fn is_enabled(&self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    fn enable(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Autostart is not supported on this platform yet",
+        ))
+    }
+
+    fn disable(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod xdg {
+    use super::AutostartBackend;
+    use std::path::PathBuf;
+    use std::{env, fs, io};
+
+    // https://specifications.freedesktop.org/autostart-spec/latest/
+    pub struct XdgAutostart {
+        path: Option<PathBuf>,
+    }
+
+    impl XdgAutostart {
+        pub fn new() -> XdgAutostart {
+            XdgAutostart {
+                path: dirs::config_dir().map(|dir| dir.join("autostart").join("wanipop.desktop")),
+            }
+        }
+
+        fn path(&self) -> io::Result<&PathBuf> {
+            self.path
+                .as_ref()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find config directory"))
+        }
+    }
+
+    // Quote a path for Exec. Inside quotes ", `, $ and \ need a backslash, % is doubled so
+    // it isn't a field code, and then every backslash is doubled again for the string
+    // escaping that desktop files apply before the quoting.
+    fn exec_arg(path: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in path.chars() {
+            match c {
+                '"' | '`' | '$' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                '%' => quoted.push_str("%%"),
+                _ => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted.replace('\\', "\\\\")
+    }
+
+    impl AutostartBackend for XdgAutostart {
+        fn is_enabled(&self) -> io::Result<bool> {
+            let path = self.path()?;
+            if !path.exists() {
+                return Ok(false);
+            }
+
+            // Desktop environments let users switch an entry off without deleting it
+            let entry = fs::read_to_string(path)?;
+            Ok(!entry
+                .lines()
+                .any(|line| line.trim() == "Hidden=true" || line.trim() == "X-GNOME-Autostart-enabled=false"))
+        }
+
+        fn enable(&self) -> io::Result<()> {
+            let path = self.path()?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            // AppImages run from a temporary mount, the stable path is in $APPIMAGE
+            let exe = match env::var_os("APPIMAGE") {
+                Some(appimage) => PathBuf::from(appimage),
+                None => env::current_exe()?,
+            };
+
+            let entry = format!(
+                "[Desktop Entry]\n\
+                 Type=Application\n\
+                 Name=WaniPOP!\n\
+                 Comment=Bugs you to do a few WaniKani reviews\n\
+                 Exec={}\n\
+                 Terminal=false\n\
+                 X-GNOME-Autostart-enabled=true\n",
+                exec_arg(&exe.display().to_string())
+            );
+            fs::write(path, entry)
+        }
+
+        fn disable(&self) -> io::Result<()> {
+            let path = self.path()?;
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::exec_arg;
+
+        #[test]
+        fn plain_paths_are_just_quoted() {
+            assert_eq!(exec_arg("/home/user/WaniPOP.AppImage"), "\"/home/user/WaniPOP.AppImage\"");
+        }
+
+        #[test]
+        fn special_characters_are_escaped() {
+            assert_eq!(exec_arg("/home/a\"b"), r#""/home/a\\"b""#);
+            assert_eq!(exec_arg("/home/$USER/`x`"), r#""/home/\\$USER/\\`x\\`""#);
+            assert_eq!(exec_arg(r"/home/a\b"), r#""/home/a\\\\b""#);
+            assert_eq!(exec_arg("/home/100%/app"), "\"/home/100%%/app\"");
+        }
+    }
+}
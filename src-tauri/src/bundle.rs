@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::{fs, io};
+
+use crate::config::{is_patchable, write_private_atomic, FieldError, WanipopConfig};
+use crate::history::HistoryEntry;
+
+// Bump when the bundle layout changes in a way older versions can't read
+pub const BUNDLE_VERSION: u32 = 1;
+
+// Everything needed to set WaniPOP up the same way on another machine, in one file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SettingsBundle {
+    pub bundle_version: u32,
+    pub exported_at: DateTime<Utc>,
+    // Only the settings an import may change, see config::is_patchable
+    pub settings: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wanikani_api_key: Option<String>,
+    // Local review history, merged into the existing history on import
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<HistoryEntry>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SettingChange {
+    pub field: String,
+    pub current: Value,
+    pub imported: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportPreview {
+    pub bundle_version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub changes: Vec<SettingChange>,
+    // Settings this version of WaniPOP doesn't know about, they'll be left out
+    pub ignored_fields: Vec<String>,
+    pub includes_api_key: bool,
+    pub history_entries: usize,
+    // Problems that would stop the import, empty if it can go ahead
+    pub errors: Vec<FieldError>,
+}
+
+impl SettingsBundle {
+    pub fn from_config(config: &WanipopConfig, include_api_key: bool) -> SettingsBundle {
+        let settings = match serde_json::to_value(config) {
+            Ok(Value::Object(fields)) => fields
+                .into_iter()
+                .filter(|(field, _)| is_patchable(field))
+                .collect(),
+            _ => Map::new(),
+        };
+
+        SettingsBundle {
+            bundle_version: BUNDLE_VERSION,
+            exported_at: Utc::now(),
+            settings,
+            wanikani_api_key: if include_api_key {
+                config.wanikani_api_key.clone()
+            } else {
+                None
+            },
+            history: Vec::new(),
+        }
+    }
+
+    // Written like the config, since it may hold the token
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        write_private_atomic(path, json.as_bytes())
+    }
+
+    pub fn read(path: &Path) -> io::Result<SettingsBundle> {
+        let data = fs::read_to_string(path)?;
+        let bundle: SettingsBundle = serde_json::from_str(&data)?;
+
+        if bundle.bundle_version > BUNDLE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "This bundle was made by a newer WaniPOP (bundle version {}), please update first",
+                    bundle.bundle_version
+                ),
+            ));
+        }
+        Ok(bundle)
+    }
+
+    // The settings from the bundle this version knows how to import, as a patch for the current config
+    fn patch(&self, current: &Map<String, Value>) -> Map<String, Value> {
+        self.settings
+            .iter()
+            .filter(|(field, _)| current.contains_key(*field) && is_patchable(field))
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect()
+    }
+
+    pub fn preview(&self, config: &WanipopConfig) -> ImportPreview {
+        let current = match serde_json::to_value(config) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        };
+        let patch = self.patch(&current);
+
+        let changes = patch
+            .iter()
+            .filter(|(field, imported)| current.get(*field) != Some(*imported))
+            .map(|(field, imported)| SettingChange {
+                field: field.clone(),
+                current: current.get(field).cloned().unwrap_or(Value::Null),
+                imported: imported.clone(),
+            })
+            .collect();
+        let ignored_fields = self
+            .settings
+            .keys()
+            .filter(|field| !patch.contains_key(*field))
+            .cloned()
+            .collect();
+        let errors = config.patched(&Value::Object(patch)).err().unwrap_or_default();
+
+        ImportPreview {
+            bundle_version: self.bundle_version,
+            exported_at: self.exported_at,
+            changes,
+            ignored_fields,
+            includes_api_key: self.wanikani_api_key.is_some(),
+            history_entries: self.history.len(),
+            errors,
+        }
+    }
+
+    // The config with the bundle's settings applied, checked the same way as any other update.
+    // The token isn't included here, it goes through WanipopConfig::set_api_key.
+    pub fn apply(&self, config: &WanipopConfig) -> Result<WanipopConfig, Vec<FieldError>> {
+        let current = match serde_json::to_value(config) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        };
+        config.patched(&Value::Object(self.patch(&current)))
+    }
+}
@@ -1,9 +1,28 @@
-use crate::config::WanipopConfig;
+use crate::autostart;
+use crate::bundle::{ImportPreview, SettingsBundle};
+use crate::config::{BatchPolicy, ConfigWarning, FieldError, WanipopConfig};
+use crate::export::{self, ExportOptions, ExportSummary};
+use crate::forecast::{self, Forecast, ForecastRange};
+use crate::goal::{self, GoalProgress};
+use crate::history::{History, HistoryEntry, HistoryQuery, ReviewDetails};
+use crate::leeches::{self, Leech};
+use crate::levels::{self, LevelTime};
+use crate::practice::{self, PracticeResult, PracticeSource};
+use crate::popup;
+use crate::profiles::Profiles;
+use crate::secrets::{self, SecretBackend};
+use crate::srs::{self, BatchSummary, SrsSystems};
+use crate::stats::{self, Stats, StatsRange};
+use crate::tray;
 use crate::wanikani::{self, FailedSubmittedReviewData, ReviewResult, SubmittedReviewData, SubmittedReviewDataResult};
 use crate::AppState;
 use futures::future::join_all;
+use reqwest::StatusCode;
 use serde::Serialize;
-use tauri::State;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ReviewCard {
@@ -15,49 +34,338 @@ pub struct ReviewCard {
     pub readings: Option<Vec<wanikani::Reading>>,
     pub meaning_mnemonic: Option<String>,
     pub reading_mnemonic: Option<String>,
+    pub srs_stage: u8,
+}
+
+// Pair each assignment with its subject, dropping any whose subject didn't come back
+pub fn review_cards(assignments: Vec<wanikani::Assignment>, subjects: &[wanikani::Subject]) -> Vec<ReviewCard> {
+    assignments
+        .into_iter()
+        .filter_map(|a| {
+            subjects
+                .iter()
+                .find(|s| s.id == a.data.subject_id)
+                .map(|s| ReviewCard {
+                    assignment_id: a.id,
+                    subject_id: s.id,
+                    subject_type: a.data.subject_type.clone(),
+                    characters: s.data.characters.clone(),
+                    meanings: s.data.meanings.clone(),
+                    readings: s.data.readings.clone(),
+                    meaning_mnemonic: s.data.meaning_mnemonic.clone(),
+                    reading_mnemonic: s.data.reading_mnemonic.clone(),
+                    srs_stage: a.data.srs_stage,
+                })
+        })
+        .collect()
 }
 
 // Config commands
 
 #[tauri::command]
 pub fn get_config(state: State<'_, AppState>) -> WanipopConfig {
-    state.config.lock().unwrap().clone()
+    state.config.lock().unwrap().masked()
 }
 
 #[tauri::command]
-pub fn set_api_key(state: State<'_, AppState>, key: String) -> Result<(), String> {
-    let mut cfg = state.config.lock().unwrap();
-    cfg.set_api_key(key).map_err(|e| e.to_string())
+pub fn get_config_warning(state: State<'_, AppState>) -> Option<ConfigWarning> {
+    state.config_warning.clone()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyInfo {
+    pub username: String,
+    pub level: u32,
+    pub subscription_type: String,
+    pub max_level_granted: u8,
+    // None when the permission check itself failed
+    pub can_submit_reviews: Option<bool>,
+    pub warnings: Vec<String>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum ApiKeyError {
+    // WaniKani didn't accept the token
+    Invalid(String),
+    // Couldn't ask WaniKani, the token may well be fine
+    Network(String),
+    // The token checked out but couldn't be saved
+    Storage(String),
+}
+
+// Ask WaniKani who the token belongs to and what it's allowed to do
+async fn check_api_key(client: &reqwest::Client, key: &str) -> Result<ApiKeyInfo, ApiKeyError> {
+    if key.is_empty() {
+        return Err(ApiKeyError::Invalid("The API key is empty".to_string()));
+    }
+
+    let user = wanikani::fetch_user(client, key.to_string())
+        .await
+        .map_err(|e| match e.status() {
+            Some(StatusCode::UNAUTHORIZED) => {
+                ApiKeyError::Invalid("WaniKani did not accept this API key".to_string())
+            }
+            _ => ApiKeyError::Network(format!("Could not reach WaniKani: {}", e)),
+        })?;
+
+    // The user check already passed, so a failed permission check only earns a warning
+    let mut warnings = Vec::new();
+    let can_submit_reviews = match wanikani::can_create_reviews(client, key.to_string()).await {
+        Ok(allowed) => Some(allowed),
+        Err(e) => {
+            warnings.push(format!(
+                "Couldn't verify the reviews:create permission ({}). If answers fail to send, check the key's permissions.",
+                e
+            ));
+            None
+        }
+    };
+    if can_submit_reviews == Some(false) {
+        warnings.push(NO_REVIEW_PERMISSION.to_string());
+    }
+
+    if let Some(reason) = user.pause_reason() {
+        warnings.push(format!("{}, so WaniPOP won't pop up until that changes.", reason.message()));
+    }
+
+    Ok(ApiKeyInfo {
+        username: user.username,
+        level: user.level,
+        subscription_type: user.subscription.r#type,
+        max_level_granted: user.subscription.max_level_granted,
+        can_submit_reviews,
+        warnings,
+    })
+}
+
+const NO_REVIEW_PERMISSION: &str = "This API key doesn't have the reviews:create permission, so your answers can't be sent to WaniKani. Generate a key with it enabled.";
+
+// Check the token against WaniKani before saving it, so a typo shows up here
+// rather than as a popup that never comes
 #[tauri::command]
-pub fn set_num_of_reviews_per_batch(
+pub async fn set_api_key(
+    app: AppHandle,
     state: State<'_, AppState>,
-    new_value: usize,
-) -> Result<(), String> {
+    key: String,
+) -> Result<ApiKeyInfo, ApiKeyError> {
+    let key = key.trim().to_string();
+    let info = check_api_key(&state.http_client, &key).await?;
+
+    {
+        let mut cfg = state.config.lock().unwrap();
+        cfg.set_api_key(key)
+            .map_err(|e| ApiKeyError::Storage(e.to_string()))?;
+        emit_config_changed(&app, &cfg);
+    }
+
+    Ok(info)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "details", rename_all = "snake_case")]
+pub enum ConfigUpdateError {
+    Invalid(Vec<FieldError>),
+    Storage(String),
+}
+
+// Change any number of settings at once. Nothing is saved unless the whole patch is valid.
+#[tauri::command]
+pub fn update_config(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    patch: Value,
+) -> Result<WanipopConfig, ConfigUpdateError> {
+    let updated = {
+        let mut cfg = state.config.lock().unwrap();
+        let updated = cfg.patched(&patch).map_err(ConfigUpdateError::Invalid)?;
+        updated
+            .save()
+            .map_err(|e| ConfigUpdateError::Storage(e.to_string()))?;
+        *cfg = updated.clone();
+        updated
+    };
+
+    emit_config_changed(&app, &updated);
+    Ok(updated.masked())
+}
+
+// Let the rest of the app know the settings changed
+pub fn emit_config_changed(app: &AppHandle, config: &WanipopConfig) {
+    if let Some(win) = app.get_webview_window("main") {
+        let _ = win.set_decorations(!config.hide_window_decorations);
+    }
+    let _ = app.emit("config-changed", config.masked());
+}
+
+#[tauri::command]
+pub fn set_autostart(app: AppHandle, state: State<'_, AppState>, new_value: bool) -> Result<(), String> {
+    autostart::set_enabled(new_value).map_err(|e| e.to_string())?;
     let mut cfg = state.config.lock().unwrap();
-    cfg.set_num_of_reviews_per_batch(new_value)
-        .map_err(|e| e.to_string())
+    cfg.set_autostart(new_value).map_err(|e| e.to_string())?;
+    emit_config_changed(&app, &cfg);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn set_time_between_popups_in_minutes(
+pub fn set_secret_store(
+    app: AppHandle,
     state: State<'_, AppState>,
-    new_value: usize,
+    new_value: SecretBackend,
 ) -> Result<(), String> {
     let mut cfg = state.config.lock().unwrap();
-    cfg.set_time_between_popups_in_minutes(new_value)
-        .map_err(|e| e.to_string())
+    cfg.set_secret_store(new_value)
+        .map_err(|e| e.to_string())?;
+    emit_config_changed(&app, &cfg);
+    Ok(())
 }
 
+// Settings bundle commands
+
 #[tauri::command]
-pub fn set_hide_window_decorations(
+pub fn export_settings(
     state: State<'_, AppState>,
-    new_value: bool,
+    path: String,
+    include_api_key: bool,
+    include_history: bool,
 ) -> Result<(), String> {
-    let mut cfg = state.config.lock().unwrap();
-    cfg.set_hide_window_decorations(new_value)
-        .map_err(|e| e.to_string())
+    let cfg = state.config.lock().unwrap().clone();
+    let mut bundle = SettingsBundle::from_config(&cfg, include_api_key);
+    if include_history {
+        bundle.history = History::open(&cfg.profile)
+            .and_then(|history| history.query(&HistoryQuery::default()))
+            .map_err(|e| e.to_string())?;
+    }
+    bundle.write(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn preview_import(state: State<'_, AppState>, path: String) -> Result<ImportPreview, String> {
+    let bundle = SettingsBundle::read(Path::new(&path)).map_err(|e| e.to_string())?;
+    let cfg = state.config.lock().unwrap();
+    Ok(bundle.preview(&cfg))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    pub config: WanipopConfig,
+    // About the imported token, the same ones set_api_key gives
+    pub warnings: Vec<String>,
+}
+
+// A bundled token is checked the same way set_api_key checks one
+#[tauri::command]
+pub async fn import_settings(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    include_api_key: bool,
+) -> Result<ImportResult, ConfigUpdateError> {
+    let bundle = SettingsBundle::read(Path::new(&path))
+        .map_err(|e| ConfigUpdateError::Storage(e.to_string()))?;
+
+    let api_key = match (include_api_key, &bundle.wanikani_api_key) {
+        (true, Some(key)) => Some(key.trim().to_string()),
+        _ => None,
+    };
+    let mut warnings = Vec::new();
+    if let Some(key) = &api_key {
+        match check_api_key(&state.http_client, key).await {
+            Ok(info) => warnings = info.warnings,
+            Err(ApiKeyError::Invalid(message) | ApiKeyError::Network(message) | ApiKeyError::Storage(message)) => {
+                return Err(ConfigUpdateError::Invalid(vec![FieldError {
+                    field: "wanikani_api_key".to_string(),
+                    message,
+                }]))
+            }
+        }
+    }
+
+    // set_api_key saves the config too, and only once the token is stored, so a
+    // token that can't be stored leaves the old settings on disk
+    let updated = {
+        let mut cfg = state.config.lock().unwrap();
+        let mut updated = bundle.apply(&cfg).map_err(ConfigUpdateError::Invalid)?;
+        match api_key {
+            Some(key) => updated.set_api_key(key),
+            None => updated.save(),
+        }
+        .map_err(|e| ConfigUpdateError::Storage(e.to_string()))?;
+        *cfg = updated.clone();
+        updated
+    };
+
+    // Reviews already in the history are skipped, so importing twice is harmless
+    if !bundle.history.is_empty() {
+        History::open(&updated.profile)
+            .and_then(|mut history| history.record(&bundle.history))
+            .map_err(|e| ConfigUpdateError::Storage(e.to_string()))?;
+    }
+
+    emit_config_changed(&app, &updated);
+    Ok(ImportResult {
+        config: updated.masked(),
+        warnings,
+    })
+}
+
+// Profile commands
+
+#[tauri::command]
+pub fn list_profiles() -> Profiles {
+    Profiles::load()
+}
+
+#[tauri::command]
+pub fn create_profile(app: AppHandle, name: String) -> Result<Profiles, String> {
+    let mut profiles = Profiles::load();
+    profiles.create(&name)?;
+    tray::rebuild_menu(&app);
+    Ok(profiles)
+}
+
+#[tauri::command]
+pub fn delete_profile(app: AppHandle, name: String) -> Result<Profiles, String> {
+    let mut profiles = Profiles::load();
+    profiles.delete(&name)?;
+
+    // The token isn't in the profile directory, clear it from wherever it might be
+    let account = WanipopConfig::api_key_account(&name);
+    for backend in [SecretBackend::Keyring, SecretBackend::EncryptedFile] {
+        if let Some(store) = secrets::store_for(backend, &account) {
+            let _ = store.delete();
+        }
+    }
+
+    tray::rebuild_menu(&app);
+    Ok(profiles)
+}
+
+#[tauri::command]
+pub fn switch_profile(app: AppHandle, name: String) -> Result<WanipopConfig, String> {
+    activate_profile(&app, &name)
+}
+
+// Load the profile's config in place of the current one and tell everyone about it
+pub fn activate_profile(app: &AppHandle, name: &str) -> Result<WanipopConfig, String> {
+    let mut profiles = Profiles::load();
+    profiles.set_active(name)?;
+
+    let (mut config, warning) = WanipopConfig::load_or_create(name);
+    if let Some(warning) = warning {
+        eprintln!("{}", warning);
+    }
+    if let Err(e) = autostart::sync_config(&mut config) {
+        eprintln!("Could not check autostart entry: {}", e);
+    }
+
+    *app.state::<AppState>().config.lock().unwrap() = config.clone();
+
+    println!("Switched to profile {}", name);
+    tray::rebuild_menu(app);
+    emit_config_changed(app, &config);
+    let _ = app.emit("profile-changed", name);
+    Ok(config.masked())
 }
 
 // User commands
@@ -125,14 +433,19 @@ fn get_api_key(state: &State<'_, AppState>) -> Result<String, String> {
 pub async fn get_review_batch(state: State<'_, AppState>) -> Result<Vec<ReviewCard>, String> {
     // grab config
     let client = state.http_client.clone();
-    let (api_key, batch_size) = {
+    let (api_key, batch_size, batch_policy, profile) = {
         let cfg = state.config.lock().unwrap();
         let key = cfg.wanikani_api_key.clone().ok_or("API key not set")?;
-        (key, cfg.num_of_reviews_per_batch)
+        (key, cfg.num_of_reviews_per_batch, cfg.batch_policy, cfg.profile.clone())
     };
-    drop(state);
 
-    // 1. fetch summary
+    // 1. the levels their subscription covers, and the summary
+    let max_level_granted = state
+        .account_status(&api_key)
+        .await
+        .map_err(|e| format!("User error: {}", e))?
+        .max_level_granted;
+    drop(state);
     let summary = wanikani::fetch_summary(&client, api_key.clone())
         .await
         .map_err(|e| format!("Summary error: {}", e))?;
@@ -145,8 +458,17 @@ pub async fn get_review_batch(state: State<'_, AppState>) -> Result<Vec<ReviewCa
         .find(|b| b.available_at <= now && !b.subject_ids.is_empty())
         .ok_or("No reviews available right now")?;
 
-    // 3. choose up to batch_size random subject IDs
+    // 3. choose up to batch_size subject IDs, at random or leeches first
     let mut ids = bucket.subject_ids;
+    if batch_policy == BatchPolicy::LeechesFirst {
+        match leeches::review_statistics(&client, api_key.clone(), &profile).await {
+            Ok(statistics) => {
+                let scores = leeches::leech_scores(statistics.values());
+                ids = leeches::leeches_first(ids, &scores, batch_size);
+            }
+            Err(e) => eprintln!("Could not fetch review statistics, picking at random: {}", e),
+        }
+    }
     if ids.len() > batch_size {
         use rand::seq::SliceRandom;
         let mut rng = rand::rng();
@@ -157,37 +479,20 @@ pub async fn get_review_batch(state: State<'_, AppState>) -> Result<Vec<ReviewCa
     // println!("Randomly chose these reviews to do:\n{:#?}", ids);
 
     // 4. fetch assignments & subjects in one go
-    let assignments = wanikani::fetch_assignments_for_subjects(&client, api_key.clone(), &ids)
+    let assignments = wanikani::fetch_assignments_for_subjects(&client, api_key.clone(), &ids, max_level_granted)
         .await
         .map_err(|e| format!("Assignments error: {}", e))?;
 
     // println!("Fetched assignments:\n{:#?}", assignments);
 
-    let subjects = wanikani::fetch_subjects(&client, api_key.clone(), &ids)
+    let subjects = wanikani::fetch_subjects(&client, api_key.clone(), &ids, max_level_granted)
         .await
         .map_err(|e| format!("Subjects error: {}", e))?;
 
     // println!("Fetched subjects:\n{:#?}", assignments);
 
     // 5. zip them into ReviewCard
-    let cards = assignments
-        .into_iter()
-        .filter_map(|a| {
-            subjects
-                .iter()
-                .find(|s| s.id == a.data.subject_id)
-                .map(|s| ReviewCard {
-                    assignment_id: a.id,
-                    subject_id: s.id,
-                    subject_type: a.data.subject_type.clone(),
-                    characters: s.data.characters.clone(),
-                    meanings: s.data.meanings.clone(),
-                    readings: s.data.readings.clone(),
-                    meaning_mnemonic: s.data.meaning_mnemonic.clone(),
-                    reading_mnemonic: s.data.reading_mnemonic.clone(),
-                })
-        })
-        .collect();
+    let cards = review_cards(assignments, &subjects);
 
     // println!("Cards to send to frontend:\n{:#?}", cards);
 
@@ -196,14 +501,16 @@ pub async fn get_review_batch(state: State<'_, AppState>) -> Result<Vec<ReviewCa
 
 #[tauri::command]
 pub async fn submit_review_batch(
+    app: AppHandle,
     state: State<'_, AppState>,
     payload: Vec<ReviewResult>,
+    details: Option<Vec<ReviewDetails>>,
 ) -> Result<Vec<SubmittedReviewDataResult>, String> {
     // grab config
     let client = state.http_client.clone();
-    let api_key = {
+    let (api_key, profile) = {
         let cfg = state.config.lock().unwrap();
-        cfg.wanikani_api_key.clone().ok_or("API key not set")?
+        (cfg.wanikani_api_key.clone().ok_or("API key not set")?, cfg.profile.clone())
     };
     drop(state);
 
@@ -220,6 +527,11 @@ pub async fn submit_review_batch(
                     ending_srs_stage: response.data.ending_srs_stage,
                     incorrect_meaning_answers: response.data.incorrect_meaning_answers,
                     incorrect_reading_answers: response.data.incorrect_reading_answers,
+                    starting_srs_stage_name: None,
+                    ending_srs_stage_name: None,
+                    next_review_at: None,
+                    passed: None,
+                    spaced_repetition_system_id: response.data.spaced_repetition_system_id,
                 }),
                 Err(e) => SubmittedReviewDataResult::Failure(FailedSubmittedReviewData {
                     assignment_id: item.assignment_id,
@@ -229,7 +541,7 @@ pub async fn submit_review_batch(
         }
     });
 
-    let results: Vec<SubmittedReviewDataResult> = join_all(tasks).await;
+    let mut results: Vec<SubmittedReviewDataResult> = join_all(tasks).await;
 
     // Check if there were any failures
     let has_failures = results.iter().any(|r| matches!(r, SubmittedReviewDataResult::Failure(_)));
@@ -239,5 +551,242 @@ pub async fn submit_review_batch(
         println!("{} reviews failed. Check individual results for details.", failure_count);
     }
 
+    // Name the stages, work out when each item is back and sum up how the batch went
+    match SrsSystems::load(&client, api_key.clone()).await {
+        Ok(systems) => {
+            let mut summary = BatchSummary::default();
+            let mut submitted = 0;
+            for result in results.iter_mut() {
+                let SubmittedReviewDataResult::Success(data) = result else {
+                    continue;
+                };
+                if let Some(system) = systems.get(data.spaced_repetition_system_id) {
+                    srs::name_stages(data, system);
+                    srs::schedule(data, system);
+                    summary.add(data, system);
+                    submitted += 1;
+                }
+            }
+            if submitted > 0 {
+                popup::notify(&app, &summary.message());
+            }
+        }
+        Err(e) => eprintln!("Could not load SRS systems: {}", e),
+    }
+
+    // Keep what was answered, the results are only shown once otherwise
+    let details: HashMap<u64, ReviewDetails> = details
+        .unwrap_or_default()
+        .into_iter()
+        .map(|d| (d.assignment_id, d))
+        .collect();
+    let entries: Vec<HistoryEntry> = results
+        .iter()
+        .filter_map(|r| match r {
+            SubmittedReviewDataResult::Success(data) => {
+                Some(HistoryEntry::new(data, details.get(&data.assignment_id)))
+            }
+            SubmittedReviewDataResult::Failure(_) => None,
+        })
+        .collect();
+    if let Err(e) = History::open(&profile).and_then(|mut history| history.record(&entries)) {
+        eprintln!("Could not save review history: {}", e);
+    }
+
     Ok(results)
 }
+
+#[tauri::command]
+pub async fn get_level_history(state: State<'_, AppState>) -> Result<Vec<LevelTime>, String> {
+    let client = state.http_client.clone();
+    let api_key = {
+        let cfg = state.config.lock().unwrap();
+        cfg.wanikani_api_key.clone().ok_or("API key not set")?
+    };
+    drop(state);
+
+    let progressions = wanikani::fetch_level_progressions(&client, api_key)
+        .await
+        .map_err(|e| format!("Level progressions error: {}", e))?;
+    Ok(levels::level_times(&progressions, chrono::Utc::now()))
+}
+
+#[tauri::command]
+pub async fn get_forecast(
+    state: State<'_, AppState>,
+    range: ForecastRange,
+) -> Result<Forecast, String> {
+    let client = state.http_client.clone();
+    let api_key = {
+        let cfg = state.config.lock().unwrap();
+        cfg.wanikani_api_key.clone().ok_or("API key not set")?
+    };
+    drop(state);
+
+    forecast::fetch(&client, api_key, range).await
+}
+
+#[tauri::command]
+pub async fn get_practice_batch(
+    state: State<'_, AppState>,
+    source: PracticeSource,
+) -> Result<Vec<ReviewCard>, String> {
+    let client = state.http_client.clone();
+    let (api_key, batch_size, profile) = {
+        let cfg = state.config.lock().unwrap();
+        let key = cfg.wanikani_api_key.clone().ok_or("API key not set")?;
+        (key, cfg.num_of_reviews_per_batch, cfg.profile.clone())
+    };
+    let max_level_granted = state
+        .account_status(&api_key)
+        .await
+        .map_err(|e| format!("User error: {}", e))?
+        .max_level_granted;
+    drop(state);
+
+    practice::build_batch(&client, api_key, max_level_granted, &profile, source, batch_size).await
+}
+
+// Practice answers are only recorded locally, WaniKani never sees them
+#[tauri::command]
+pub fn submit_practice_batch(
+    state: State<'_, AppState>,
+    results: Vec<PracticeResult>,
+) -> Result<Vec<SubmittedReviewDataResult>, String> {
+    let profile = state.config.lock().unwrap().profile.clone();
+
+    let reviews: Vec<SubmittedReviewData> = results.iter().map(PracticeResult::to_review_data).collect();
+    let entries: Vec<HistoryEntry> = results
+        .iter()
+        .zip(&reviews)
+        .map(|(result, review)| result.to_history_entry(review))
+        .collect();
+    History::open(&profile)
+        .and_then(|mut history| history.record(&entries))
+        .map_err(|e| format!("Could not save practice results: {}", e))?;
+
+    Ok(reviews.into_iter().map(SubmittedReviewDataResult::Success).collect())
+}
+
+// The subjects with the worst leech scores, see leeches.rs
+#[tauri::command]
+pub async fn get_top_leeches(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<Leech>, String> {
+    let client = state.http_client.clone();
+    let (api_key, profile) = {
+        let cfg = state.config.lock().unwrap();
+        let key = cfg.wanikani_api_key.clone().ok_or("API key not set")?;
+        (key, cfg.profile.clone())
+    };
+    let max_level_granted = state
+        .account_status(&api_key)
+        .await
+        .map_err(|e| format!("User error: {}", e))?
+        .max_level_granted;
+    drop(state);
+
+    let statistics = leeches::review_statistics(&client, api_key.clone(), &profile)
+        .await
+        .map_err(|e| format!("Review statistics error: {}", e))?;
+
+    let mut ranked = leeches::ranked(&leeches::leech_scores(statistics.values()));
+    ranked.truncate(limit.unwrap_or(20));
+    if ranked.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids: Vec<u64> = ranked.iter().map(|(id, _)| *id).collect();
+    let subjects = wanikani::fetch_subjects(&client, api_key, &ids, max_level_granted)
+        .await
+        .map_err(|e| format!("Subjects error: {}", e))?;
+
+    Ok(ranked
+        .into_iter()
+        .filter_map(|(subject_id, score)| {
+            let stats = statistics.get(&subject_id)?;
+            let subject = subjects.iter().find(|s| s.id == subject_id);
+            Some(Leech {
+                subject_id,
+                subject_type: stats.subject_type.clone(),
+                characters: subject.and_then(|s| s.data.characters.clone()),
+                meaning: subject.and_then(|s| {
+                    s.data.meanings.iter().find(|m| m.primary).map(|m| m.meaning.clone())
+                }),
+                score,
+                meaning_incorrect: stats.meaning_incorrect,
+                reading_incorrect: stats.reading_incorrect,
+                percentage_correct: stats.percentage_correct,
+            })
+        })
+        .collect())
+}
+
+// Without a limit only this many come back, page through the rest with offset
+const DEFAULT_HISTORY_LIMIT: u32 = 500;
+
+// Sync commands run on the main thread, so the database work goes on a blocking thread
+#[tauri::command]
+pub async fn get_review_history(
+    state: State<'_, AppState>,
+    mut query: HistoryQuery,
+) -> Result<Vec<HistoryEntry>, String> {
+    let profile = state.config.lock().unwrap().profile.clone();
+    query.limit.get_or_insert(DEFAULT_HISTORY_LIMIT);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let history = History::open(&profile).map_err(|e| e.to_string())?;
+        history.query(&query).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_stats(state: State<'_, AppState>, range: StatsRange) -> Result<Stats, String> {
+    let (profile, api_key) = {
+        let cfg = state.config.lock().unwrap();
+        (cfg.profile.clone(), cfg.wanikani_api_key.clone())
+    };
+
+    // Only for naming the stages, the stats themselves come from the local history
+    let systems = match api_key {
+        Some(api_key) => match SrsSystems::load(&state.http_client, api_key).await {
+            Ok(systems) => Some(systems),
+            Err(e) => {
+                eprintln!("Could not load SRS systems: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let system = systems.as_ref().and_then(|systems| systems.get(None));
+
+    let history = History::open(&profile).map_err(|e| e.to_string())?;
+    stats::load(&history, range, system).map_err(|e| e.to_string())
+}
+
+// Today's reviews against the daily goals set in the config
+#[tauri::command]
+pub async fn get_goal_progress(state: State<'_, AppState>) -> Result<GoalProgress, String> {
+    let config = state.config.lock().unwrap().clone();
+    goal::load(&config, &state.http_client).await
+}
+
+// Write the history to a CSV or JSON Lines file, see export.rs for the schema.
+// A long history takes a while, so it's written from a blocking thread.
+#[tauri::command]
+pub async fn export_history(
+    state: State<'_, AppState>,
+    path: String,
+    options: ExportOptions,
+) -> Result<ExportSummary, String> {
+    let profile = state.config.lock().unwrap().profile.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        export::export_to_path(&profile, &options, Path::new(&path)).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
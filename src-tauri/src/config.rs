@@ -1,14 +1,207 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
 use std::io::{self, Write};
-use std::{fs, path::PathBuf};
+use std::fs;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+use crate::profiles::{self, DEFAULT_PROFILE};
+use crate::secrets::{self, SecretBackend};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Bump this and add a step to MIGRATIONS whenever a field is renamed or changes meaning.
+// New fields don't need a migration, they're filled in from Default when loading.
+pub const CONFIG_VERSION: u32 = 1;
+
+// Set from --config or WANIPOP_CONFIG, for the profile active at launch
+static CONFIG_PATH_OVERRIDE: OnceLock<(String, PathBuf)> = OnceLock::new();
+
+// MIGRATIONS[n] upgrades a config from version n to version n + 1
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[
+    // 0 -> 1: configs from before versioning, same shape with no config_version
+    |_| {},
+];
+
+// Fields with side effects outside config.json, they have their own setters
+const PATCH_EXCLUDED_FIELDS: &[&str] = &["config_version", "wanikani_api_key", "secret_store", "autostart"];
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+// Whether a field can be changed through patched(), i.e. by the UI, imports or the environment
+pub fn is_patchable(field: &str) -> bool {
+    !PATCH_EXCLUDED_FIELDS.contains(&field)
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> FieldError {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+// Said once at startup when the settings in use aren't the ones in the config file
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigWarning {
+    pub title: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Why a config file couldn't be loaded. Only Invalid means there's something wrong with the file.
+#[derive(Debug)]
+enum LoadError {
+    Read(io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Read(e) => write!(f, "{}", e),
+            LoadError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// What to do when the scheduler finds reviews waiting
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PopupBehavior {
+    // Show the window and steal focus
+    #[default]
+    Focus,
+    // Only send a notification, the session is opened from the tray. Configs from when
+    // there was a show_without_focus option get this, showing a window steals focus on
+    // most platforms anyway.
+    #[serde(alias = "show_without_focus")]
+    NotifyOnly,
+}
+
+// How a review batch is picked from the reviews that are due
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchPolicy {
+    #[default]
+    Random,
+    // Due leeches first, worst first, then random picks to fill the batch. See leeches.rs
+    LeechesFirst,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct WanipopConfig {
+    pub config_version: u32,
+
+    // Which profile this config belongs to, decided by where it's stored rather than its contents
+    #[serde(skip)]
+    pub profile: String,
+
+    // Fields whose value came from the environment, see overrides.rs. Saving keeps
+    // whatever is on disk for these instead.
+    #[serde(skip)]
+    pub overrides: Vec<String>,
+
     pub num_of_reviews_per_batch: usize,
     pub time_between_popups_in_minutes: usize,
+    // Only written to config.json with the plain file backend, see secrets.rs
     pub wanikani_api_key: Option<String>,
+    pub secret_store: SecretBackend,
 
     pub hide_window_decorations: bool,
+
+    // After the machine wakes up, wait this long before checking for reviews
+    pub resume_grace_period_in_seconds: usize,
+
+    pub popup_behavior: PopupBehavior,
+    pub batch_policy: BatchPolicy,
+
+    // Daily goals, see goal.rs. Either, both or neither can be set.
+    pub daily_review_goal: Option<u32>,
+    // Local hour, 0-23, by which no reviews should be waiting
+    pub clear_backlog_by_hour: Option<u8>,
+    // When behind on a goal from this local hour on, pop up every goal_catch_up_interval_in_minutes
+    pub goal_catch_up_from_hour: u8,
+    pub goal_catch_up_interval_in_minutes: usize,
+
+    // Mirrors whether an autostart entry exists, see autostart.rs
+    pub autostart: bool,
+}
+
+// Written out by hand so the API key never ends up in logs
+impl fmt::Debug for WanipopConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WanipopConfig")
+            .field("config_version", &self.config_version)
+            .field("profile", &self.profile)
+            .field("overrides", &self.overrides)
+            .field("num_of_reviews_per_batch", &self.num_of_reviews_per_batch)
+            .field("time_between_popups_in_minutes", &self.time_between_popups_in_minutes)
+            .field("wanikani_api_key", &self.wanikani_api_key.as_ref().map(|_| "<redacted>"))
+            .field("secret_store", &self.secret_store)
+            .field("hide_window_decorations", &self.hide_window_decorations)
+            .field("resume_grace_period_in_seconds", &self.resume_grace_period_in_seconds)
+            .field("popup_behavior", &self.popup_behavior)
+            .field("batch_policy", &self.batch_policy)
+            .field("daily_review_goal", &self.daily_review_goal)
+            .field("clear_backlog_by_hour", &self.clear_backlog_by_hour)
+            .field("goal_catch_up_from_hour", &self.goal_catch_up_from_hour)
+            .field("goal_catch_up_interval_in_minutes", &self.goal_catch_up_interval_in_minutes)
+            .field("autostart", &self.autostart)
+            .finish()
+    }
+}
+
+// Write to a temporary file next to `path` and rename it over the original, so a crash
+// mid-write leaves the old file intact. The file may hold the API key, so on Unix it's
+// only readable by the user.
+pub fn write_private_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let written = open_private(&tmp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp_path, path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let _ = fs::File::open(parent).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
+
+fn open_private(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // mode only applies to newly created files, tighten a leftover one too
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+
+    #[cfg(not(unix))]
+    options.open(path)
 }
 
 impl Default for WanipopConfig {
@@ -25,71 +218,617 @@ impl WanipopConfig {
         hide_window_decorations: bool,
     ) -> WanipopConfig {
         WanipopConfig {
+            config_version: CONFIG_VERSION,
+            profile: DEFAULT_PROFILE.to_string(),
+            overrides: Vec::new(),
             num_of_reviews_per_batch,
             time_between_popups_in_minutes,
             wanikani_api_key,
+            secret_store: SecretBackend::default(),
             hide_window_decorations,
+            resume_grace_period_in_seconds: 60,
+            popup_behavior: PopupBehavior::default(),
+            batch_policy: BatchPolicy::default(),
+            daily_review_goal: None,
+            clear_backlog_by_hour: None,
+            goal_catch_up_from_hour: 18,
+            goal_catch_up_interval_in_minutes: 15,
+            autostart: false,
         }
     }
 
-    pub fn config_path() -> PathBuf {
-        let config_dir = dirs::config_dir().expect("Could not find config directory");
-        config_dir.join("wanipop").join("config.json")
+    fn default_for(profile: &str) -> WanipopConfig {
+        WanipopConfig {
+            profile: profile.to_string(),
+            ..WanipopConfig::default()
+        }
     }
 
-    pub fn load_or_create() -> io::Result<Self> {
-        let path = Self::config_path();
+    pub fn config_path(profile: &str) -> PathBuf {
+        match CONFIG_PATH_OVERRIDE.get() {
+            Some((overridden, path)) if overridden == profile => path.clone(),
+            _ => profiles::profile_dir(profile).join("config.json"),
+        }
+    }
+
+    pub fn override_config_path(profile: &str, path: PathBuf) {
+        let _ = CONFIG_PATH_OVERRIDE.set((profile.to_string(), path));
+    }
 
-        if path.exists() {
-            let data = fs::read_to_string(&path)?;
-            let config: WanipopConfig = serde_json::from_str(&data)?;
-            Ok(config)
+    // Name the API token is stored under in the secret store. The default profile
+    // keeps the name it had before profiles existed.
+    pub fn api_key_account(profile: &str) -> String {
+        if profile == DEFAULT_PROFILE {
+            "wanikani_api_key".to_string()
         } else {
-            let config = WanipopConfig::default();
-            config.save()?;
-            Ok(config)
+            format!("wanikani_api_key.{}", profile)
         }
     }
 
+    // Never fails: a config that can't be understood is backed up and replaced with the
+    // defaults, and the returned warning says what happened. One that merely can't be read
+    // right now is left where it is, and so is one that couldn't be backed up.
+    pub fn load_or_create(profile: &str) -> (Self, Option<ConfigWarning>) {
+        let path = Self::config_path(profile);
+
+        if !path.exists() {
+            let config = WanipopConfig::default_for(profile);
+            if let Err(e) = config.save() {
+                eprintln!("Could not save new config: {}", e);
+            }
+            return (config, None);
+        }
+
+        match Self::load(&path, profile) {
+            Ok(config) => (config, None),
+            Err(LoadError::Read(e)) => {
+                // Could be a permission problem or a locked file, the contents may be fine
+                let warning = ConfigWarning {
+                    title: "WaniPOP! couldn't read its settings".to_string(),
+                    message: format!(
+                        "Your config could not be read ({}). The default settings are being used for now, the file was left alone.",
+                        e
+                    ),
+                };
+                eprintln!("{}", warning);
+                (WanipopConfig::default_for(profile), Some(warning))
+            }
+            Err(LoadError::Invalid(e)) => {
+                let backup = path.with_extension(format!(
+                    "json.corrupt-{}",
+                    chrono::Local::now().format("%Y%m%d%H%M%S")
+                ));
+                let config = WanipopConfig::default_for(profile);
+
+                // Without a backup the file is the only copy, so it isn't overwritten
+                let message = match fs::rename(&path, &backup) {
+                    Ok(()) => {
+                        if let Err(e) = config.save() {
+                            eprintln!("Could not save new config: {}", e);
+                        }
+                        format!(
+                            "Your config could not be loaded ({}). It was moved to {} and the default settings are being used.",
+                            e,
+                            backup.display()
+                        )
+                    }
+                    Err(rename_error) => format!(
+                        "Your config could not be loaded ({}) or backed up ({}). The default settings are being used for now, the file was left alone.",
+                        e, rename_error
+                    ),
+                };
+                let warning = ConfigWarning {
+                    title: "WaniPOP! settings were reset".to_string(),
+                    message,
+                };
+                eprintln!("{}", warning);
+                (config, Some(warning))
+            }
+        }
+    }
+
+    // Like load_or_create, but nothing on disk or in the secret store changes: no file is
+    // created, upgraded in place or moved aside, and a token in config.json stays there.
+    // For --print-config and --export, which only look.
+    pub fn read(profile: &str) -> (Self, Option<String>) {
+        Self::read_from(&Self::config_path(profile), profile)
+    }
+
+    fn read_from(path: &Path, profile: &str) -> (Self, Option<String>) {
+        if !path.exists() {
+            return (WanipopConfig::default_for(profile), None);
+        }
+
+        match Self::parse(path) {
+            Ok((mut config, _)) => {
+                config.profile = profile.to_string();
+                if config.wanikani_api_key.is_none() {
+                    config.read_api_key();
+                }
+                (config, None)
+            }
+            Err(e) => (
+                WanipopConfig::default_for(profile),
+                Some(format!("Your config could not be loaded ({}), using the defaults.", e)),
+            ),
+        }
+    }
+
+    // Read, migrate and fill in a config file, in memory only
+    fn parse(path: &Path) -> Result<(Self, bool), LoadError> {
+        let data = fs::read_to_string(path).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => LoadError::Invalid(e.to_string()),
+            _ => LoadError::Read(e),
+        })?;
+        let value: Value = serde_json::from_str(&data).map_err(|e| LoadError::Invalid(e.to_string()))?;
+        Self::from_value(value).map_err(|e| LoadError::Invalid(e.to_string()))
+    }
+
+    fn load(path: &Path, profile: &str) -> Result<Self, LoadError> {
+        let (mut config, upgraded) = Self::parse(path)?;
+        config.profile = profile.to_string();
+        config.load_api_key();
+
+        if upgraded {
+            println!("Upgraded config to version {}", CONFIG_VERSION);
+            // The upgrade is redone next launch, no reason to throw away a good config
+            if let Err(e) = config.save() {
+                eprintln!("Could not save upgraded config: {}", e);
+            }
+        }
+        Ok(config)
+    }
+
+    // Migrate an on-disk config to the current version and fill in anything missing from Default.
+    // Also reports whether anything had to change, so the caller knows to save.
+    pub fn from_value(value: Value) -> io::Result<(Self, bool)> {
+        let Value::Object(mut fields) = value else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "config is not a JSON object"));
+        };
+
+        let version = fields
+            .get("config_version")
+            .and_then(Value::as_u64)
+            .unwrap_or(0) as usize;
+        if version > CONFIG_VERSION as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("config version {} is newer than this version of WaniPOP supports", version),
+            ));
+        }
+
+        for migration in &MIGRATIONS[version..] {
+            migration(&mut fields);
+        }
+        fields.insert("config_version".to_string(), Value::from(CONFIG_VERSION));
+
+        let Value::Object(defaults) = serde_json::to_value(WanipopConfig::default())? else {
+            unreachable!("WanipopConfig always serializes to an object");
+        };
+        let mut upgraded = version < CONFIG_VERSION as usize;
+        for (key, default) in defaults {
+            if !fields.contains_key(&key) {
+                fields.insert(key, default);
+                upgraded = true;
+            }
+        }
+
+        let config = serde_json::from_value(Value::Object(fields))?;
+        Ok((config, upgraded))
+    }
+
     pub fn save(&self) -> io::Result<()> {
-        let path = Self::config_path();
+        let path = Self::config_path(&self.profile);
 
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        let json = serde_json::to_string_pretty(self)?;
-        let mut file = fs::File::create(path)?;
-        file.write_all(json.as_bytes())?;
-        Ok(())
+        let mut on_disk = self.clone();
+        if self.secret_store != SecretBackend::PlainFile {
+            on_disk.wanikani_api_key = None;
+        }
+
+        let mut json = serde_json::to_value(&on_disk)?;
+        if !self.overrides.is_empty() {
+            self.keep_saved_values(&path, &mut json);
+        }
+
+        let json = serde_json::to_string_pretty(&json)?;
+        write_private_atomic(&path, json.as_bytes())
+    }
+
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if !(5..=30).contains(&self.num_of_reviews_per_batch) {
+            errors.push(FieldError::new("num_of_reviews_per_batch", "Must be between 5 and 30"));
+        }
+        let interval_is_valid = (5..=24 * 60).contains(&self.time_between_popups_in_minutes);
+        if !interval_is_valid {
+            errors.push(FieldError::new(
+                "time_between_popups_in_minutes",
+                "Must be between 5 minutes and a day",
+            ));
+        }
+        if self.resume_grace_period_in_seconds > 60 * 60 {
+            errors.push(FieldError::new("resume_grace_period_in_seconds", "Must be an hour or less"));
+        } else if interval_is_valid
+            && self.resume_grace_period_in_seconds >= self.time_between_popups_in_minutes * 60
+        {
+            errors.push(FieldError::new(
+                "resume_grace_period_in_seconds",
+                "Must be shorter than the time between popups",
+            ));
+        }
+        if self.daily_review_goal.is_some_and(|goal| !(1..=10_000).contains(&goal)) {
+            errors.push(FieldError::new("daily_review_goal", "Must be between 1 and 10000 reviews"));
+        }
+        if self.clear_backlog_by_hour.is_some_and(|hour| hour > 23) {
+            errors.push(FieldError::new("clear_backlog_by_hour", "Must be an hour between 0 and 23"));
+        }
+        if self.goal_catch_up_from_hour > 23 {
+            errors.push(FieldError::new("goal_catch_up_from_hour", "Must be an hour between 0 and 23"));
+        }
+        if !(5..=24 * 60).contains(&self.goal_catch_up_interval_in_minutes) {
+            errors.push(FieldError::new(
+                "goal_catch_up_interval_in_minutes",
+                "Must be between 5 minutes and a day",
+            ));
+        }
+
+        errors
+    }
+
+    // Apply a partial update, e.g. {"num_of_reviews_per_batch": 10}. Returns the updated
+    // config without touching this one, or every problem with the patch at once.
+    pub fn patched(&self, patch: &Value) -> Result<WanipopConfig, Vec<FieldError>> {
+        let Value::Object(patch) = patch else {
+            return Err(vec![FieldError::new("", "Expected an object of fields to change")]);
+        };
+        let Ok(Value::Object(current)) = serde_json::to_value(self) else {
+            unreachable!("WanipopConfig always serializes to an object");
+        };
+
+        let mut errors = Vec::new();
+        let mut merged = current.clone();
+        for (field, value) in patch {
+            if !current.contains_key(field) {
+                errors.push(FieldError::new(field, "Unknown setting"));
+                continue;
+            }
+            if !is_patchable(field) {
+                errors.push(FieldError::new(field, "Can't be changed with a config update"));
+                continue;
+            }
+
+            // Check each field on its own so type errors point at the right one
+            let mut single = current.clone();
+            single.insert(field.clone(), value.clone());
+            if let Err(e) = serde_json::from_value::<WanipopConfig>(Value::Object(single)) {
+                errors.push(FieldError::new(field, e.to_string()));
+                continue;
+            }
+
+            merged.insert(field.clone(), value.clone());
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut config: WanipopConfig = serde_json::from_value(Value::Object(merged))
+            .map_err(|e| vec![FieldError::new("", e.to_string())])?;
+        config.profile = self.profile.clone();
+        config.overrides = self
+            .overrides
+            .iter()
+            .filter(|field| !patch.contains_key(*field))
+            .cloned()
+            .collect();
+        // Only what the patch broke. A value that was already out of range on disk, e.g. from
+        // a hand edit, shouldn't block changes to unrelated settings.
+        let existing = self.validate();
+        let errors: Vec<FieldError> = config
+            .validate()
+            .into_iter()
+            .filter(|error| !existing.contains(error))
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(config)
+    }
+
+    // Put back what's on disk for overridden fields, or leave them out so they come from Default
+    fn keep_saved_values(&self, path: &Path, json: &mut Value) {
+        let saved = fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str::<Value>(&data).ok());
+
+        let Value::Object(fields) = json else {
+            return;
+        };
+        for field in &self.overrides {
+            match saved.as_ref().and_then(|saved| saved.get(field)) {
+                Some(value) => fields.insert(field.clone(), value.clone()),
+                None => fields.remove(field),
+            };
+        }
+    }
+
+    // A copy that's safe to hand to the webview
+    pub fn masked(&self) -> WanipopConfig {
+        let mut masked = self.clone();
+        masked.wanikani_api_key = self.wanikani_api_key.as_deref().map(secrets::mask);
+        masked
+    }
+
+    // Fill in the API key from the secret store. A key still sitting in config.json
+    // from an older version is moved into the store instead.
+    fn load_api_key(&mut self) {
+        if secrets::store_for(self.secret_store, &Self::api_key_account(&self.profile)).is_none() {
+            return;
+        }
+
+        if let Some(key) = self.wanikani_api_key.clone() {
+            println!("Moving API key out of config.json");
+            if let Err(e) = self.set_api_key(key) {
+                eprintln!("Could not move API key into the secret store, keeping it in config.json: {}", e);
+                self.secret_store = SecretBackend::PlainFile;
+            }
+            return;
+        }
+
+        self.read_api_key();
+    }
+
+    fn read_api_key(&mut self) {
+        let Some(store) = secrets::store_for(self.secret_store, &Self::api_key_account(&self.profile)) else {
+            return;
+        };
+
+        match store.get() {
+            Ok(key) => self.wanikani_api_key = key,
+            Err(e) => eprintln!("Could not read API key from {:?}: {}", self.secret_store, e),
+        }
     }
 
     pub fn set_api_key(&mut self, key: String) -> io::Result<()> {
+        // Setting a key by hand replaces one from WANIPOP_API_KEY
+        self.overrides.retain(|field| field != "wanikani_api_key");
+
+        if let Some(store) = secrets::store_for(self.secret_store, &Self::api_key_account(&self.profile)) {
+            if let Err(e) = store.set(&key) {
+                if self.secret_store != SecretBackend::Keyring {
+                    return Err(e);
+                }
+
+                // No keyring on this machine (or it's locked), fall back to the encrypted file
+                eprintln!("Keyring unavailable, storing API key in an encrypted file: {}", e);
+                secrets::store_for(SecretBackend::EncryptedFile, &Self::api_key_account(&self.profile))
+                    .unwrap()
+                    .set(&key)?;
+                self.secret_store = SecretBackend::EncryptedFile;
+            }
+        }
+
         self.wanikani_api_key = Some(key);
         self.save()
     }
 
-    pub fn set_num_of_reviews_per_batch(
-        &mut self,
-        new_num_of_reviews_per_batch: usize,
-    ) -> io::Result<()> {
-        self.num_of_reviews_per_batch = new_num_of_reviews_per_batch;
-        self.save()
+    // Move the API key to another backend
+    pub fn set_secret_store(&mut self, new_secret_store: SecretBackend) -> io::Result<()> {
+        if new_secret_store == self.secret_store {
+            return Ok(());
+        }
+
+        let old_store = secrets::store_for(self.secret_store, &Self::api_key_account(&self.profile));
+        if let (Some(store), Some(key)) = (secrets::store_for(new_secret_store, &Self::api_key_account(&self.profile)), &self.wanikani_api_key) {
+            store.set(key)?;
+        }
+
+        self.secret_store = new_secret_store;
+        self.save()?;
+
+        if let Some(store) = old_store {
+            store.delete()?;
+        }
+        Ok(())
     }
 
-    pub fn set_time_between_popups_in_minutes(
-        &mut self,
-        new_time_between_popups_in_minutes: usize,
-    ) -> io::Result<()> {
-        self.time_between_popups_in_minutes = new_time_between_popups_in_minutes;
+    pub fn set_autostart(&mut self, new_autostart: bool) -> io::Result<()> {
+        self.autostart = new_autostart;
         self.save()
     }
+}
 
-    pub fn set_hide_window_decorations(
-        &mut self,
-        new_hide_window_decorations: bool,
-    ) -> io::Result<()> {
-        self.hide_window_decorations = new_hide_window_decorations;
-        self.save()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // A fresh directory under the system temp dir, emptied first
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wanipop-config-tests-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn migrates_unversioned_config() {
+        let (config, upgraded) = WanipopConfig::from_value(json!({
+            "num_of_reviews_per_batch": 12,
+            "time_between_popups_in_minutes": 30,
+            "wanikani_api_key": null,
+            "hide_window_decorations": true,
+        }))
+        .unwrap();
+
+        assert!(upgraded);
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        assert_eq!(config.num_of_reviews_per_batch, 12);
+        assert_eq!(config.time_between_popups_in_minutes, 30);
+        assert!(config.hide_window_decorations);
+    }
+
+    #[test]
+    fn fills_in_missing_fields() {
+        let Value::Object(mut fields) = serde_json::to_value(WanipopConfig::default()).unwrap() else {
+            unreachable!();
+        };
+        let (_, upgraded) = WanipopConfig::from_value(Value::Object(fields.clone())).unwrap();
+        assert!(!upgraded);
+
+        fields.remove("popup_behavior");
+        fields.remove("resume_grace_period_in_seconds");
+        fields.insert("num_of_reviews_per_batch".to_string(), json!(20));
+        let (config, upgraded) = WanipopConfig::from_value(Value::Object(fields)).unwrap();
+        assert!(upgraded);
+        assert_eq!(config.popup_behavior, PopupBehavior::default());
+        assert_eq!(config.resume_grace_period_in_seconds, 60);
+        assert_eq!(config.num_of_reviews_per_batch, 20);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(WanipopConfig::from_value(json!({ "config_version": CONFIG_VERSION + 1 })).is_err());
+        assert!(WanipopConfig::from_value(json!([1, 2, 3])).is_err());
+    }
+
+    // The only test using the path override, it can be set once per process
+    #[test]
+    fn recovers_from_corrupt_and_unreadable_configs() {
+        let dir = test_dir("recover");
+        let path = dir.join("config.json");
+        let profile = "config-tests";
+        WanipopConfig::override_config_path(profile, path.clone());
+
+        // Not JSON: moved aside and replaced with the defaults
+        fs::write(&path, "{ this is not json").unwrap();
+        let (config, warning) = WanipopConfig::load_or_create(profile);
+        assert!(warning.is_some());
+        assert_eq!(config.num_of_reviews_per_batch, WanipopConfig::default().num_of_reviews_per_batch);
+        let backups: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains(".corrupt-"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(backups[0].path()).unwrap(), "{ this is not json");
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["config_version"], json!(CONFIG_VERSION));
+
+        // Can't be read at all: left alone, nothing is backed up or written
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+        let (_, warning) = WanipopConfig::load_or_create(profile);
+        assert!(warning.is_some());
+        assert!(path.is_dir());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn failed_temp_write_keeps_old_file() {
+        let dir = test_dir("temp-write");
+        let path = dir.join("config.json");
+        fs::write(&path, "old").unwrap();
+        // Opening the temporary file fails when something else is in the way
+        fs::create_dir(dir.join("config.json.tmp")).unwrap();
+
+        assert!(write_private_atomic(&path, b"new").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!dir.join("config.json.tmp").is_file());
+    }
+
+    #[test]
+    fn failed_rename_leaves_no_temp_file() {
+        let dir = test_dir("rename");
+        let path = dir.join("config.json");
+        // A file can't be renamed over a non-empty directory
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), "old").unwrap();
+
+        assert!(write_private_atomic(&path, b"new").is_err());
+        assert_eq!(fs::read_to_string(path.join("keep")).unwrap(), "old");
+        assert!(!dir.join("config.json.tmp").exists());
+    }
+
+    #[test]
+    fn replaces_contents() {
+        let dir = test_dir("replace");
+        let path = dir.join("config.json");
+        fs::write(&path, "old").unwrap();
+
+        write_private_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(!dir.join("config.json.tmp").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn written_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("mode");
+        let path = dir.join("config.json");
+        write_private_atomic(&path, b"{}").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // Also when replacing a file that was readable by others
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_private_atomic(&path, b"{}").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn patch_errors_name_the_patched_field() {
+        let config = WanipopConfig::default();
+        let errors = config.patched(&json!({ "num_of_reviews_per_batch": 100 })).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "num_of_reviews_per_batch");
+
+        // Already invalid on disk: unrelated changes still go through
+        let config = WanipopConfig {
+            num_of_reviews_per_batch: 100,
+            ..WanipopConfig::default()
+        };
+        let patched = config.patched(&json!({ "hide_window_decorations": true })).unwrap();
+        assert!(patched.hide_window_decorations);
+
+        // But a patch that makes things worse elsewhere is still caught
+        let errors = WanipopConfig::default()
+            .patched(&json!({ "time_between_popups_in_minutes": 1 }))
+            .unwrap_err();
+        assert_eq!(errors[0].field, "time_between_popups_in_minutes");
+    }
+
+    #[test]
+    fn read_leaves_files_alone() {
+        let dir = test_dir("read");
+        let path = dir.join("config.json");
+
+        // Missing: defaults, and nothing created
+        let (config, warning) = WanipopConfig::read_from(&path, "read-tests");
+        assert!(warning.is_none());
+        assert_eq!(config.profile, "read-tests");
+        assert!(!path.exists());
+
+        // Old version with a plain token: upgraded in memory only, token left in the file
+        let old = r#"{"num_of_reviews_per_batch": 7, "time_between_popups_in_minutes": 20, "wanikani_api_key": "token", "hide_window_decorations": false}"#;
+        fs::write(&path, old).unwrap();
+        let (config, warning) = WanipopConfig::read_from(&path, "read-tests");
+        assert!(warning.is_none());
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        assert_eq!(config.num_of_reviews_per_batch, 7);
+        assert_eq!(config.wanikani_api_key.as_deref(), Some("token"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), old);
+
+        // Corrupt: a warning, but the file stays put
+        fs::write(&path, "not json").unwrap();
+        let (_, warning) = WanipopConfig::read_from(&path, "read-tests");
+        assert!(warning.is_some());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not json");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
     }
 }
@@ -0,0 +1,107 @@
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager};
+use tokio::time::sleep;
+
+use crate::commands::emit_config_changed;
+use crate::config::{is_patchable, FieldError, WanipopConfig};
+use crate::secrets::SecretBackend;
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Work out what an edited config.json changes about the running config.
+// Returns None when nothing changed, which is also what our own saves look like.
+pub fn reload(current: &WanipopConfig, data: &str) -> Result<Option<WanipopConfig>, Vec<FieldError>> {
+    let value: Value =
+        serde_json::from_str(data).map_err(|e| vec![FieldError::new("", e.to_string())])?;
+    let (on_disk, _) =
+        WanipopConfig::from_value(value).map_err(|e| vec![FieldError::new("", e.to_string())])?;
+
+    let (Ok(Value::Object(current_fields)), Ok(Value::Object(disk_fields))) =
+        (serde_json::to_value(current), serde_json::to_value(&on_disk))
+    else {
+        return Ok(None);
+    };
+
+    // Environment overrides keep winning, and fields like the token have their own setters
+    let patch: Map<String, Value> = disk_fields
+        .into_iter()
+        .filter(|(field, value)| {
+            is_patchable(field)
+                && !current.overrides.contains(field)
+                && current_fields.get(field) != Some(value)
+        })
+        .collect();
+
+    // With the plain file backend the token is in config.json too
+    let key_changed = current.secret_store == SecretBackend::PlainFile
+        && !current.overrides.iter().any(|field| field == "wanikani_api_key")
+        && on_disk.wanikani_api_key.is_some()
+        && on_disk.wanikani_api_key != current.wanikani_api_key;
+
+    if patch.is_empty() && !key_changed {
+        return Ok(None);
+    }
+
+    let mut updated = current.patched(&Value::Object(patch))?;
+    if key_changed {
+        updated.wanikani_api_key = on_disk.wanikani_api_key;
+    }
+    Ok(Some(updated))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// Pick up hand edits and dotfile syncs to the active profile's config.json
+pub async fn watch_loop(app_handle: AppHandle) {
+    let mut watched: Option<(PathBuf, Option<SystemTime>)> = None;
+
+    loop {
+        sleep(POLL_INTERVAL).await;
+
+        let state = app_handle.state::<AppState>();
+        let path = WanipopConfig::config_path(&state.config.lock().unwrap().profile);
+        let last_modified = modified(&path);
+
+        // Start over quietly after a profile switch
+        let changed = match &watched {
+            Some((watched_path, watched_modified)) if *watched_path == path => {
+                *watched_modified != last_modified
+            }
+            _ => false,
+        };
+        watched = Some((path.clone(), last_modified));
+        if !changed {
+            continue;
+        }
+
+        let Ok(data) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let updated = {
+            let mut cfg = state.config.lock().unwrap();
+            match reload(&cfg, &data) {
+                Ok(Some(updated)) => {
+                    *cfg = updated.clone();
+                    updated
+                }
+                Ok(None) => continue,
+                Err(errors) => {
+                    for error in errors {
+                        eprintln!("Ignoring edit to {}: {} {}", path.display(), error.field, error.message);
+                    }
+                    continue;
+                }
+            }
+        };
+
+        println!("Reloaded config after it changed on disk");
+        emit_config_changed(&app_handle, &updated);
+    }
+}
@@ -0,0 +1,518 @@
+// Exports of the local review history, for analysis outside WaniPOP. From the app with
+// the export_history command, or from the command line:
+//
+//   wanipop --export reviews.csv [--data reviews|attempts|daily_stats] [--format csv|jsonl]
+//           [--since YYYY-MM-DD] [--until YYYY-MM-DD] [--subject-type kanji,vocabulary]
+//           [--include-practice]
+//
+// The format follows the file extension unless given, .jsonl is JSON Lines and anything
+// else CSV. Dates are local and both ends are included. Practice reviews are left out
+// unless asked for.
+//
+// Schema, version 1. Columns only ever get added at the end, a change to an existing
+// one bumps EXPORT_SCHEMA_VERSION. CSV has a header row with these names, JSON Lines has
+// one object per line with the same keys. Times are RFC 3339 in UTC, empty/null when unknown.
+//
+//   reviews      one row per review
+//     created_at, assignment_id, subject_id, subject_type, characters,
+//     starting_srs_stage, ending_srs_stage, incorrect_meaning_answers,
+//     incorrect_reading_answers, time_taken_ms, practice
+//
+//   attempts     one row per answer typed, reviews from before attempts were kept have none
+//     review_created_at, assignment_id, subject_id, subject_type, question_type
+//     (meaning or reading), answer, correct, time_taken_ms, answered_at, practice
+//
+//   daily_stats  one row per local day with reviews, oldest first
+//     date (YYYY-MM-DD), reviews, correct (no wrong answers), incorrect,
+//     accuracy_percent, time_taken_ms
+
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::history::{History, HistoryEntry, HistoryQuery, QuestionType};
+use crate::stats::{has_reading, local_day};
+
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<ExportFormat> {
+        match value {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" => Some(ExportFormat::Jsonl),
+            _ => None,
+        }
+    }
+
+    pub fn for_path(path: &Path) -> ExportFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl") => ExportFormat::Jsonl,
+            _ => ExportFormat::Csv,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportData {
+    #[default]
+    Reviews,
+    Attempts,
+    DailyStats,
+}
+
+impl ExportData {
+    pub fn parse(value: &str) -> Option<ExportData> {
+        match value {
+            "reviews" => Some(ExportData::Reviews),
+            "attempts" => Some(ExportData::Attempts),
+            "daily_stats" => Some(ExportData::DailyStats),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ExportOptions {
+    pub data: ExportData,
+    // Defaults to CSV from the app, and to the file extension from the command line
+    pub format: Option<ExportFormat>,
+    // Local days, both included
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    // Every type when empty
+    pub subject_types: Vec<String>,
+    pub include_practice: bool,
+}
+
+fn local_midnight(day: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&day.and_time(NaiveTime::MIN))
+        .earliest()
+        .map(|midnight| midnight.with_timezone(&Utc))
+}
+
+impl ExportOptions {
+    fn query(&self) -> HistoryQuery {
+        HistoryQuery {
+            since: self.since.and_then(local_midnight),
+            until: self
+                .until
+                .and_then(|day| day.checked_add_days(Days::new(1)))
+                .and_then(local_midnight),
+            subject_types: self.subject_types.clone(),
+            practice: if self.include_practice { None } else { Some(false) },
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReviewRow<'a> {
+    created_at: DateTime<Utc>,
+    assignment_id: u64,
+    subject_id: u64,
+    subject_type: Option<&'a str>,
+    characters: Option<&'a str>,
+    starting_srs_stage: u8,
+    ending_srs_stage: u8,
+    incorrect_meaning_answers: u8,
+    incorrect_reading_answers: u8,
+    time_taken_ms: u64,
+    practice: bool,
+}
+
+impl ReviewRow<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "created_at",
+        "assignment_id",
+        "subject_id",
+        "subject_type",
+        "characters",
+        "starting_srs_stage",
+        "ending_srs_stage",
+        "incorrect_meaning_answers",
+        "incorrect_reading_answers",
+        "time_taken_ms",
+        "practice",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.created_at.to_rfc3339(),
+            self.assignment_id.to_string(),
+            self.subject_id.to_string(),
+            self.subject_type.unwrap_or_default().to_string(),
+            self.characters.unwrap_or_default().to_string(),
+            self.starting_srs_stage.to_string(),
+            self.ending_srs_stage.to_string(),
+            self.incorrect_meaning_answers.to_string(),
+            self.incorrect_reading_answers.to_string(),
+            self.time_taken_ms.to_string(),
+            self.practice.to_string(),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct AttemptRow<'a> {
+    review_created_at: DateTime<Utc>,
+    assignment_id: u64,
+    subject_id: u64,
+    subject_type: Option<&'a str>,
+    question_type: QuestionType,
+    answer: &'a str,
+    correct: bool,
+    time_taken_ms: u64,
+    answered_at: DateTime<Utc>,
+    practice: bool,
+}
+
+impl AttemptRow<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "review_created_at",
+        "assignment_id",
+        "subject_id",
+        "subject_type",
+        "question_type",
+        "answer",
+        "correct",
+        "time_taken_ms",
+        "answered_at",
+        "practice",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.review_created_at.to_rfc3339(),
+            self.assignment_id.to_string(),
+            self.subject_id.to_string(),
+            self.subject_type.unwrap_or_default().to_string(),
+            self.question_type.as_str().to_string(),
+            self.answer.to_string(),
+            self.correct.to_string(),
+            self.time_taken_ms.to_string(),
+            self.answered_at.to_rfc3339(),
+            self.practice.to_string(),
+        ]
+    }
+}
+
+#[derive(Serialize, Default)]
+struct DailyStatsRow {
+    date: NaiveDate,
+    reviews: u32,
+    correct: u32,
+    incorrect: u32,
+    accuracy_percent: Option<f64>,
+    time_taken_ms: u64,
+}
+
+impl DailyStatsRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "date",
+        "reviews",
+        "correct",
+        "incorrect",
+        "accuracy_percent",
+        "time_taken_ms",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.date.to_string(),
+            self.reviews.to_string(),
+            self.correct.to_string(),
+            self.incorrect.to_string(),
+            self.accuracy_percent.map(|p| format!("{:.1}", p)).unwrap_or_default(),
+            self.time_taken_ms.to_string(),
+        ]
+    }
+}
+
+// Quote a CSV field when it needs it, doubling any quotes inside
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+struct RowWriter<W: Write> {
+    out: W,
+    format: ExportFormat,
+    rows: usize,
+}
+
+impl<W: Write> RowWriter<W> {
+    fn new(mut out: W, format: ExportFormat, columns: &[&str]) -> io::Result<RowWriter<W>> {
+        if format == ExportFormat::Csv {
+            writeln!(out, "{}", columns.join(","))?;
+        }
+        Ok(RowWriter { out, format, rows: 0 })
+    }
+
+    fn write(&mut self, row: &impl Serialize, fields: Vec<String>) -> io::Result<()> {
+        match self.format {
+            ExportFormat::Csv => {
+                let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                writeln!(self.out, "{}", line.join(","))?;
+            }
+            ExportFormat::Jsonl => {
+                serde_json::to_writer(&mut self.out, row)?;
+                writeln!(self.out)?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<usize> {
+        self.out.flush()?;
+        Ok(self.rows)
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    History(rusqlite::Error),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "Could not write the export: {}", e),
+            ExportError::History(e) => write!(f, "Could not read the review history: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for ExportError {
+    fn from(e: rusqlite::Error) -> Self {
+        ExportError::History(e)
+    }
+}
+
+// Write the rows picked by the options to `out`, returns how many were written
+pub fn export(
+    history: &History,
+    options: &ExportOptions,
+    format: ExportFormat,
+    out: impl Write,
+) -> Result<usize, ExportError> {
+    let query = options.query();
+
+    match options.data {
+        ExportData::Reviews => {
+            let mut writer = RowWriter::new(out, format, ReviewRow::COLUMNS)?;
+            history.for_each(&query, |entry| {
+                let row = ReviewRow {
+                    created_at: entry.created_at,
+                    assignment_id: entry.assignment_id,
+                    subject_id: entry.subject_id,
+                    subject_type: entry.subject_type.as_deref(),
+                    characters: entry.characters.as_deref(),
+                    starting_srs_stage: entry.starting_srs_stage,
+                    ending_srs_stage: entry.ending_srs_stage,
+                    incorrect_meaning_answers: entry.incorrect_meaning_answers,
+                    incorrect_reading_answers: entry.incorrect_reading_answers,
+                    time_taken_ms: entry.time_taken_ms,
+                    practice: entry.practice,
+                };
+                writer.write(&row, row.fields()).map_err(ExportError::from)
+            })?;
+            Ok(writer.finish()?)
+        }
+        ExportData::Attempts => {
+            let mut writer = RowWriter::new(out, format, AttemptRow::COLUMNS)?;
+            history.for_each(&query, |entry| {
+                for attempt in &entry.attempts {
+                    let row = AttemptRow {
+                        review_created_at: entry.created_at,
+                        assignment_id: entry.assignment_id,
+                        subject_id: entry.subject_id,
+                        subject_type: entry.subject_type.as_deref(),
+                        question_type: attempt.question_type,
+                        answer: &attempt.answer,
+                        correct: attempt.correct,
+                        time_taken_ms: attempt.time_taken_ms,
+                        answered_at: attempt.answered_at,
+                        practice: entry.practice,
+                    };
+                    writer.write(&row, row.fields())?;
+                }
+                Ok::<(), ExportError>(())
+            })?;
+            Ok(writer.finish()?)
+        }
+        // Only one row per day is kept while reading, however long the history
+        ExportData::DailyStats => {
+            let mut days: BTreeMap<NaiveDate, DailyStatsRow> = BTreeMap::new();
+            history.for_each(&query, |entry: HistoryEntry| {
+                let date = local_day(entry.created_at);
+                let day = days.entry(date).or_insert_with(|| DailyStatsRow {
+                    date,
+                    ..Default::default()
+                });
+                day.reviews += 1;
+                if entry.incorrect_meaning_answers == 0
+                    && (!has_reading(&entry) || entry.incorrect_reading_answers == 0)
+                {
+                    day.correct += 1;
+                } else {
+                    day.incorrect += 1;
+                }
+                day.time_taken_ms += entry.time_taken_ms;
+                Ok::<(), ExportError>(())
+            })?;
+
+            let mut writer = RowWriter::new(out, format, DailyStatsRow::COLUMNS)?;
+            for day in days.values_mut() {
+                day.accuracy_percent = Some(day.correct as f64 * 100.0 / day.reviews as f64);
+                writer.write(&*day, day.fields())?;
+            }
+            Ok(writer.finish()?)
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExportSummary {
+    pub rows: usize,
+    pub schema_version: u32,
+}
+
+pub fn export_to_path(
+    profile: &str,
+    options: &ExportOptions,
+    path: &Path,
+) -> Result<ExportSummary, ExportError> {
+    let format = options.format.unwrap_or_else(|| ExportFormat::for_path(path));
+    let history = History::open(profile)?;
+    let out = BufWriter::new(File::create(path)?);
+    let rows = export(&history, options, format, out)?;
+    Ok(ExportSummary {
+        rows,
+        schema_version: EXPORT_SCHEMA_VERSION,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn test_history(name: &str) -> History {
+        let path = std::env::temp_dir().join(format!("wanipop-export-tests-{}.sqlite3", name));
+        let _ = fs::remove_file(&path);
+        History::open_at(&path).unwrap()
+    }
+
+    fn entry(assignment_id: u64, subject_type: &str, incorrect_meaning: u8, incorrect_reading: u8) -> HistoryEntry {
+        let noon = Local
+            .from_local_datetime(&NaiveDate::from_ymd_opt(2026, 3, 10).unwrap().and_hms_opt(12, 0, 0).unwrap())
+            .unwrap();
+        HistoryEntry {
+            assignment_id,
+            subject_id: assignment_id,
+            subject_type: Some(subject_type.to_string()),
+            characters: Some("日, \"sun\"".to_string()),
+            starting_srs_stage: 1,
+            ending_srs_stage: 2,
+            incorrect_meaning_answers: incorrect_meaning,
+            incorrect_reading_answers: incorrect_reading,
+            time_taken_ms: 1000,
+            created_at: noon.with_timezone(&Utc),
+            attempts: Vec::new(),
+            practice: false,
+        }
+    }
+
+    fn export_csv(history: &History, data: ExportData) -> String {
+        let options = ExportOptions {
+            data,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        export(history, &options, ExportFormat::Csv, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn quotes_csv_fields_that_need_it() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\rlf"), "\"cr\rlf\"");
+    }
+
+    // The columns are documented as a stable schema, changing them means a new EXPORT_SCHEMA_VERSION
+    #[test]
+    fn header_rows_stay_the_same() {
+        let history = test_history("headers");
+        let header = |data| export_csv(&history, data).lines().next().unwrap().to_string();
+
+        assert_eq!(EXPORT_SCHEMA_VERSION, 1);
+        assert_eq!(
+            header(ExportData::Reviews),
+            "created_at,assignment_id,subject_id,subject_type,characters,starting_srs_stage,\
+             ending_srs_stage,incorrect_meaning_answers,incorrect_reading_answers,time_taken_ms,practice"
+        );
+        assert_eq!(
+            header(ExportData::Attempts),
+            "review_created_at,assignment_id,subject_id,subject_type,question_type,answer,correct,\
+             time_taken_ms,answered_at,practice"
+        );
+        assert_eq!(
+            header(ExportData::DailyStats),
+            "date,reviews,correct,incorrect,accuracy_percent,time_taken_ms"
+        );
+    }
+
+    #[test]
+    fn review_rows_are_quoted() {
+        let mut history = test_history("reviews");
+        history.record(&[entry(1, "kanji", 0, 0)]).unwrap();
+
+        let csv = export_csv(&history, ExportData::Reviews);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.contains(",kanji,\"日, \"\"sun\"\"\",1,2,"));
+    }
+
+    #[test]
+    fn daily_stats_ignore_reading_misses_without_a_reading_question() {
+        let mut history = test_history("daily");
+        history
+            .record(&[
+                entry(1, "kanji", 0, 0),
+                entry(2, "kanji", 0, 1),
+                entry(3, "radical", 0, 1),
+                entry(4, "radical", 1, 0),
+            ])
+            .unwrap();
+
+        let csv = export_csv(&history, ExportData::DailyStats);
+        assert_eq!(csv.lines().nth(1), Some("2026-03-10,4,2,2,50.0,4000"));
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::{DateTime, Duration, DurationRound, Local, NaiveTime, SecondsFormat, TimeZone, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::srs::SrsSystems;
+use crate::wanikani::{self, SpacedRepetitionSystemData};
+
+// Subject ids per assignments request, a long backlog would make the URL too long otherwise
+const SUBJECT_IDS_PER_REQUEST: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastRange {
+    // The next 24 hours, hour by hour, from the summary
+    Day,
+    // The next 7 days, day by day, from the assignments
+    Week,
+}
+
+#[derive(Serialize, Default, Clone, Copy, Debug)]
+pub struct GroupCounts {
+    pub apprentice: u32,
+    pub guru: u32,
+    pub master: u32,
+    pub enlightened: u32,
+    pub total: u32,
+}
+
+impl GroupCounts {
+    fn add(&mut self, srs_stage: u8, system: &SpacedRepetitionSystemData) {
+        match system.stage_group(srs_stage) {
+            "Apprentice" => self.apprentice += 1,
+            "Guru" => self.guru += 1,
+            "Master" => self.master += 1,
+            "Enlightened" => self.enlightened += 1,
+            // Locked and burned items never come up, so the total stays the sum of the groups
+            _ => return,
+        }
+        self.total += 1;
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ForecastSlot {
+    pub starts_at: DateTime<Utc>,
+    // Local time for hourly slots, local date for daily ones
+    pub label: String,
+    pub counts: GroupCounts,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Forecast {
+    pub range: ForecastRange,
+    // Already due, not included in any slot
+    pub available_now: GroupCounts,
+    pub slots: Vec<ForecastSlot>,
+}
+
+fn slot_starts(range: ForecastRange, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    match range {
+        ForecastRange::Day => {
+            let hour = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+            (0..24).map(|i| hour + Duration::hours(i)).collect()
+        }
+        ForecastRange::Week => {
+            let today = now.with_timezone(&Local).date_naive();
+            (0..7)
+                .filter_map(|i| {
+                    let day = today + Duration::days(i);
+                    Local
+                        .from_local_datetime(&day.and_time(NaiveTime::MIN))
+                        .earliest()
+                        .map(|midnight| midnight.with_timezone(&Utc))
+                })
+                .collect()
+        }
+    }
+}
+
+// Sort upcoming reviews, as (available_at, srs_stage), into slots. Assignments don't say
+// which SRS system they're on, so the stages are grouped by the regular one.
+pub fn bucket(
+    range: ForecastRange,
+    reviews: &[(DateTime<Utc>, u8)],
+    now: DateTime<Utc>,
+    system: &SpacedRepetitionSystemData,
+) -> Forecast {
+    let starts = slot_starts(range, now);
+    let mut slots: Vec<ForecastSlot> = starts
+        .iter()
+        .map(|start| ForecastSlot {
+            starts_at: *start,
+            label: match range {
+                ForecastRange::Day => start.with_timezone(&Local).format("%H:%M").to_string(),
+                ForecastRange::Week => start.with_timezone(&Local).format("%a %d").to_string(),
+            },
+            counts: GroupCounts::default(),
+        })
+        .collect();
+    let end = match range {
+        ForecastRange::Day => starts.last().map(|start| *start + Duration::hours(1)),
+        ForecastRange::Week => starts.last().map(|start| *start + Duration::days(1)),
+    };
+
+    let mut available_now = GroupCounts::default();
+    for (available_at, srs_stage) in reviews {
+        if *available_at <= now {
+            available_now.add(*srs_stage, system);
+            continue;
+        }
+        if end.is_some_and(|end| *available_at >= end) {
+            continue;
+        }
+        if let Some(slot) = slots.iter_mut().rev().find(|slot| slot.starts_at <= *available_at) {
+            slot.counts.add(*srs_stage, system);
+        }
+    }
+
+    Forecast {
+        range,
+        available_now,
+        slots,
+    }
+}
+
+pub async fn fetch(client: &Client, api_key: String, range: ForecastRange) -> Result<Forecast, String> {
+    let now = Utc::now();
+    let systems = SrsSystems::load(client, api_key.clone())
+        .await
+        .map_err(|e| format!("SRS systems error: {}", e))?;
+    let system = systems.get(None).ok_or("No SRS systems")?;
+
+    let reviews: Vec<(DateTime<Utc>, u8)> = match range {
+        // The summary already groups the next day by hour, it just doesn't say which stage
+        // anything is at, so look that up from the assignments
+        ForecastRange::Day => {
+            let summary = wanikani::fetch_summary(client, api_key.clone())
+                .await
+                .map_err(|e| format!("Summary error: {}", e))?;
+            let subject_ids: Vec<u64> = summary
+                .reviews
+                .iter()
+                .flat_map(|bucket| bucket.subject_ids.iter().copied())
+                .collect();
+            let mut stages: HashMap<u64, u8> = HashMap::new();
+            for chunk in subject_ids.chunks(SUBJECT_IDS_PER_REQUEST) {
+                let filters = format!(
+                    "subject_ids={}",
+                    chunk.iter().map(ToString::to_string).collect::<Vec<_>>().join(",")
+                );
+                let assignments = wanikani::fetch_assignments(client, api_key.clone(), &filters)
+                    .await
+                    .map_err(|e| format!("Assignments error: {}", e))?;
+                stages.extend(assignments.into_iter().map(|a| (a.data.subject_id, a.data.srs_stage)));
+            }
+            // Without an assignment there's no stage to count it under, so it's left out
+            // rather than counted as locked
+            summary
+                .reviews
+                .iter()
+                .flat_map(|bucket| {
+                    bucket
+                        .subject_ids
+                        .iter()
+                        .filter_map(|id| Some((bucket.available_at, *stages.get(id)?)))
+                })
+                .collect()
+        }
+        ForecastRange::Week => {
+            let until = now + Duration::days(8);
+            let filters = format!(
+                "started=true&burned=false&available_before={}",
+                until.to_rfc3339_opts(SecondsFormat::Secs, true)
+            );
+            wanikani::fetch_assignments(client, api_key, &filters)
+                .await
+                .map_err(|e| format!("Assignments error: {}", e))?
+                .into_iter()
+                .filter_map(|a| Some((a.data.available_at?, a.data.srs_stage)))
+                .collect()
+        }
+    };
+
+    Ok(bucket(range, &reviews, now, system))
+}
@@ -0,0 +1,198 @@
+// Daily goals: a number of reviews a day, an empty queue by a set hour, or both.
+// Progress is counted from the local history, so only reviews done in WaniPOP count.
+
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveTime, TimeZone, Timelike, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::config::WanipopConfig;
+use crate::history::History;
+use crate::stats::local_day;
+use crate::tray;
+use crate::wanikani;
+use crate::AppState;
+
+// How many days of counts come back with the progress, today included
+const RECENT_DAYS: u64 = 7;
+
+// With a backlog deadline, start catching up this many hours before it at the latest
+const HOURS_BEFORE_DEADLINE: u32 = 3;
+
+#[derive(Serialize, Debug)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub reviews: u32,
+    // None when there's no review goal
+    pub met: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GoalProgress {
+    pub date: NaiveDate,
+    pub reviews_done: u32,
+    pub review_goal: Option<u32>,
+    // Reviews waiting right now, only looked up for a backlog goal
+    pub reviews_available: Option<usize>,
+    pub clear_backlog_by: Option<DateTime<Utc>>,
+    // Every goal that's set is met. Always true without goals.
+    pub met: bool,
+    // Some goal is not met yet but still can be today
+    pub behind: bool,
+    // Behind, and late enough in the day that the scheduler pops up more often
+    pub catching_up: bool,
+    // Oldest first, ending with today
+    pub recent_days: Vec<DayCount>,
+}
+
+fn has_goal(config: &WanipopConfig) -> bool {
+    config.daily_review_goal.is_some() || config.clear_backlog_by_hour.is_some()
+}
+
+fn at_hour(day: NaiveDate, hour: u8) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::from_hms_opt(hour as u32, 0, 0)?;
+    Local
+        .from_local_datetime(&day.and_time(time))
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+// Reviews per local day for the last few days, from the times in the history
+fn recent_days(review_times: &[DateTime<Utc>], today: NaiveDate, goal: Option<u32>) -> Vec<DayCount> {
+    (0..RECENT_DAYS)
+        .rev()
+        .filter_map(|ago| today.checked_sub_days(Days::new(ago)))
+        .map(|date| {
+            let reviews = review_times.iter().filter(|time| local_day(**time) == date).count() as u32;
+            DayCount {
+                date,
+                reviews,
+                met: goal.map(|goal| reviews >= goal),
+            }
+        })
+        .collect()
+}
+
+pub fn progress(
+    config: &WanipopConfig,
+    review_times: &[DateTime<Utc>],
+    reviews_available: Option<usize>,
+    now: DateTime<Local>,
+) -> GoalProgress {
+    let today = now.date_naive();
+    let recent_days = recent_days(review_times, today, config.daily_review_goal);
+    let reviews_done = recent_days.last().map_or(0, |day| day.reviews);
+    let clear_backlog_by = config.clear_backlog_by_hour.and_then(|hour| at_hour(today, hour));
+
+    let reviews_behind = config.daily_review_goal.is_some_and(|goal| reviews_done < goal);
+    // Past the deadline there's nothing left to catch up on until tomorrow
+    let backlog_behind = reviews_available.unwrap_or(0) > 0
+        && clear_backlog_by.is_some_and(|deadline| now < deadline);
+    let backlog_missed = reviews_available.unwrap_or(0) > 0
+        && clear_backlog_by.is_some_and(|deadline| now >= deadline);
+
+    // Each goal decides for itself when it's late enough to pop up more often. The backlog
+    // goal also gets going a few hours before its deadline.
+    let evening = now.hour() >= config.goal_catch_up_from_hour as u32;
+    let near_deadline = clear_backlog_by
+        .is_some_and(|deadline| now + chrono::Duration::hours(HOURS_BEFORE_DEADLINE as i64) >= deadline);
+    let behind = reviews_behind || backlog_behind;
+    let catching_up = (reviews_behind && evening) || (backlog_behind && (evening || near_deadline));
+
+    GoalProgress {
+        date: today,
+        reviews_done,
+        review_goal: config.daily_review_goal,
+        reviews_available,
+        clear_backlog_by,
+        met: !reviews_behind && !backlog_behind && !backlog_missed,
+        behind,
+        catching_up,
+        recent_days,
+    }
+}
+
+pub async fn load(config: &WanipopConfig, client: &reqwest::Client) -> Result<GoalProgress, String> {
+    let history = History::open(&config.profile).map_err(|e| e.to_string())?;
+    let review_times = history.review_times().map_err(|e| e.to_string())?;
+
+    let mut reviews_available = None;
+    if config.clear_backlog_by_hour.is_some() {
+        let api_key = config.wanikani_api_key.clone().ok_or("API key not set")?;
+        let summary = wanikani::fetch_summary(client, api_key)
+            .await
+            .map_err(|e| format!("Summary error: {}", e))?;
+        reviews_available = Some(tray::available_count(&summary.reviews, Utc::now()));
+    }
+
+    Ok(progress(config, &review_times, reviews_available, Local::now()))
+}
+
+// How long the scheduler should wait next: the catch-up interval while behind late
+// in the day, None to keep the usual one
+pub async fn catch_up_interval(app_handle: &AppHandle) -> Option<Duration> {
+    let (config, client) = {
+        let state = app_handle.state::<AppState>();
+        let config = state.config.lock().unwrap().clone();
+        (config, state.http_client.clone())
+    };
+    if !has_goal(&config) {
+        return None;
+    }
+
+    match load(&config, &client).await {
+        Ok(progress) if progress.catching_up => {
+            println!("Behind on today's goal, popping up more often");
+            Some(Duration::from_secs(config.goal_catch_up_interval_in_minutes as u64 * 60))
+        }
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Could not check goal progress: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(hour: u32) -> DateTime<Local> {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 10).unwrap();
+        Local.from_local_datetime(&day.and_hms_opt(hour, 0, 0).unwrap()).unwrap()
+    }
+
+    fn config(daily_review_goal: Option<u32>, clear_backlog_by_hour: Option<u8>) -> WanipopConfig {
+        WanipopConfig {
+            daily_review_goal,
+            clear_backlog_by_hour,
+            goal_catch_up_from_hour: 18,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn review_goal_catches_up_in_the_evening() {
+        let config = config(Some(50), None);
+        assert!(!progress(&config, &[], None, local(10)).catching_up);
+        assert!(progress(&config, &[], None, local(19)).catching_up);
+    }
+
+    #[test]
+    fn early_backlog_deadline_doesnt_hurry_the_review_goal() {
+        // The backlog is already cleared, only the review goal is behind
+        let config = config(Some(50), Some(1));
+        let progress = progress(&config, &[], Some(0), local(10));
+        assert!(progress.behind);
+        assert!(!progress.catching_up);
+    }
+
+    #[test]
+    fn backlog_goal_catches_up_before_its_deadline() {
+        let config = config(None, Some(14));
+        assert!(!progress(&config, &[], Some(5), local(10)).catching_up);
+        assert!(progress(&config, &[], Some(5), local(12)).catching_up);
+        // Missed for today, nothing to catch up on
+        assert!(!progress(&config, &[], Some(5), local(15)).catching_up);
+    }
+}
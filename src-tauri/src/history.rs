@@ -0,0 +1,544 @@
+use chrono::{DateTime, Utc};
+use rusqlite::types::ToSql;
+use rusqlite::{params, params_from_iter, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::profiles;
+use crate::wanikani::SubmittedReviewData;
+
+// Each entry upgrades the database from the version before it, the current
+// version is kept in PRAGMA user_version. Never edit one that has shipped.
+const MIGRATIONS: &[&str] = &[
+    // 1: reviews and every answer typed for them
+    "CREATE TABLE reviews (
+        id INTEGER PRIMARY KEY,
+        assignment_id INTEGER NOT NULL,
+        subject_id INTEGER NOT NULL,
+        subject_type TEXT,
+        characters TEXT,
+        starting_srs_stage INTEGER NOT NULL,
+        ending_srs_stage INTEGER NOT NULL,
+        incorrect_meaning_answers INTEGER NOT NULL,
+        incorrect_reading_answers INTEGER NOT NULL,
+        time_taken_ms INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        UNIQUE (assignment_id, created_at)
+    );
+    CREATE INDEX reviews_created_at ON reviews (created_at);
+    CREATE INDEX reviews_subject_id ON reviews (subject_id);
+    CREATE TABLE attempts (
+        id INTEGER PRIMARY KEY,
+        review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+        question_type TEXT NOT NULL,
+        answer TEXT NOT NULL,
+        correct INTEGER NOT NULL,
+        time_taken_ms INTEGER NOT NULL,
+        answered_at TEXT NOT NULL
+    );
+    CREATE INDEX attempts_review_id ON attempts (review_id);",
+    // 2: practice sessions, which never reach WaniKani, see practice.rs
+    "ALTER TABLE reviews ADD COLUMN practice INTEGER NOT NULL DEFAULT 0;",
+];
+
+const REVIEW_COLUMNS: &str = "id, assignment_id, subject_id, subject_type, characters, \
+    starting_srs_stage, ending_srs_stage, incorrect_meaning_answers, incorrect_reading_answers, \
+    time_taken_ms, created_at, practice";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionType {
+    Meaning,
+    Reading,
+}
+
+impl QuestionType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuestionType::Meaning => "meaning",
+            QuestionType::Reading => "reading",
+        }
+    }
+
+    fn from_column(value: &str) -> QuestionType {
+        match value {
+            "reading" => QuestionType::Reading,
+            _ => QuestionType::Meaning,
+        }
+    }
+}
+
+// One answer typed during a review, wrong ones included
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Attempt {
+    pub question_type: QuestionType,
+    pub answer: String,
+    pub correct: bool,
+    pub time_taken_ms: u64,
+    pub answered_at: DateTime<Utc>,
+}
+
+// What the quiz knows about a review that WaniKani doesn't, sent along with the results
+#[derive(Deserialize, Clone, Debug)]
+pub struct ReviewDetails {
+    pub assignment_id: u64,
+    pub subject_type: Option<String>,
+    pub characters: Option<String>,
+    #[serde(default)]
+    pub attempts: Vec<Attempt>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub assignment_id: u64,
+    pub subject_id: u64,
+    pub subject_type: Option<String>,
+    pub characters: Option<String>,
+    pub starting_srs_stage: u8,
+    pub ending_srs_stage: u8,
+    pub incorrect_meaning_answers: u8,
+    pub incorrect_reading_answers: u8,
+    pub time_taken_ms: u64,
+    pub created_at: DateTime<Utc>,
+    pub attempts: Vec<Attempt>,
+    // Answered in a practice session, so the SRS stages didn't change
+    #[serde(default)]
+    pub practice: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(review: &SubmittedReviewData, details: Option<&ReviewDetails>) -> HistoryEntry {
+        let attempts = details.map(|d| d.attempts.clone()).unwrap_or_default();
+
+        HistoryEntry {
+            assignment_id: review.assignment_id,
+            subject_id: review.subject_id,
+            subject_type: details.and_then(|d| d.subject_type.clone()),
+            characters: details.and_then(|d| d.characters.clone()),
+            starting_srs_stage: review.starting_srs_stage,
+            ending_srs_stage: review.ending_srs_stage,
+            incorrect_meaning_answers: review.incorrect_meaning_answers,
+            incorrect_reading_answers: review.incorrect_reading_answers,
+            time_taken_ms: attempts.iter().map(|a| a.time_taken_ms).sum(),
+            created_at: review.created_at,
+            attempts,
+            practice: false,
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<(i64, HistoryEntry)> {
+        Ok((
+            row.get(0)?,
+            HistoryEntry {
+                assignment_id: row.get(1)?,
+                subject_id: row.get(2)?,
+                subject_type: row.get(3)?,
+                characters: row.get(4)?,
+                starting_srs_stage: row.get(5)?,
+                ending_srs_stage: row.get(6)?,
+                incorrect_meaning_answers: row.get(7)?,
+                incorrect_reading_answers: row.get(8)?,
+                time_taken_ms: row.get(9)?,
+                created_at: row.get(10)?,
+                attempts: Vec::new(),
+                practice: row.get(11)?,
+            },
+        ))
+    }
+}
+
+// Filters for looking through the history, everything is optional. Newest first.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub subject_id: Option<u64>,
+    pub subject_type: Option<String>,
+    // Any of these subject types, on top of subject_type
+    pub subject_types: Vec<String>,
+    pub incorrect_only: bool,
+    // Only practice reviews, or only real ones. Both when None.
+    pub practice: Option<bool>,
+    pub limit: Option<u32>,
+    pub offset: u32,
+}
+
+// Every review submitted from this profile, kept in its directory
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    pub fn path(profile: &str) -> PathBuf {
+        profiles::profile_dir(profile).join("history.sqlite3")
+    }
+
+    pub fn open(profile: &str) -> rusqlite::Result<History> {
+        let path = Self::path(profile);
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        Self::open_at(&path)
+    }
+
+    pub fn open_at(path: &Path) -> rusqlite::Result<History> {
+        let mut conn = Connection::open(path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(History { conn })
+    }
+
+    // Saves the entries, skipping any already recorded. Returns how many were new.
+    pub fn record(&mut self, entries: &[HistoryEntry]) -> rusqlite::Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut added = 0;
+
+        for entry in entries {
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO reviews (assignment_id, subject_id, subject_type, characters,
+                    starting_srs_stage, ending_srs_stage, incorrect_meaning_answers,
+                    incorrect_reading_answers, time_taken_ms, created_at, practice)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    entry.assignment_id,
+                    entry.subject_id,
+                    entry.subject_type,
+                    entry.characters,
+                    entry.starting_srs_stage,
+                    entry.ending_srs_stage,
+                    entry.incorrect_meaning_answers,
+                    entry.incorrect_reading_answers,
+                    entry.time_taken_ms,
+                    entry.created_at,
+                    entry.practice,
+                ],
+            )?;
+            if inserted == 0 {
+                continue;
+            }
+
+            let review_id = tx.last_insert_rowid();
+            for attempt in &entry.attempts {
+                tx.execute(
+                    "INSERT INTO attempts (review_id, question_type, answer, correct, time_taken_ms, answered_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        review_id,
+                        attempt.question_type.as_str(),
+                        attempt.answer,
+                        attempt.correct,
+                        attempt.time_taken_ms,
+                        attempt.answered_at,
+                    ],
+                )?;
+            }
+            added += 1;
+        }
+
+        tx.commit()?;
+        Ok(added)
+    }
+
+    pub fn query(&self, query: &HistoryQuery) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let (sql, values) = select_reviews(query, Order::NewestFirst);
+        let mut statement = self.conn.prepare(&sql)?;
+        let rows = statement
+            .query_map(params_from_iter(values.iter()), HistoryEntry::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|(id, mut entry)| {
+                entry.attempts = self.attempts(id)?;
+                Ok(entry)
+            })
+            .collect()
+    }
+
+    // Like query, but oldest first and one entry at a time, so exporting a long
+    // history never holds all of it in memory
+    pub fn for_each<E>(
+        &self,
+        query: &HistoryQuery,
+        mut f: impl FnMut(HistoryEntry) -> Result<(), E>,
+    ) -> Result<(), E>
+    where
+        E: From<rusqlite::Error>,
+    {
+        let (sql, values) = select_reviews(query, Order::OldestFirst);
+        let mut statement = self.conn.prepare(&sql)?;
+        let mut rows = statement.query(params_from_iter(values.iter()))?;
+        while let Some(row) = rows.next()? {
+            let (id, mut entry) = HistoryEntry::from_row(row)?;
+            entry.attempts = self.attempts(id)?;
+            f(entry)?;
+        }
+        Ok(())
+    }
+
+    // When every review happened, oldest first. Practice doesn't count.
+    pub fn review_times(&self) -> rusqlite::Result<Vec<DateTime<Utc>>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT created_at FROM reviews WHERE practice = 0 ORDER BY created_at")?;
+        let times: Vec<DateTime<Utc>> = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(times)
+    }
+
+    fn attempts(&self, review_id: i64) -> rusqlite::Result<Vec<Attempt>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT question_type, answer, correct, time_taken_ms, answered_at
+             FROM attempts WHERE review_id = ?1 ORDER BY answered_at, id",
+        )?;
+        let attempts: Vec<Attempt> = statement
+            .query_map([review_id], |row| {
+                Ok(Attempt {
+                    question_type: QuestionType::from_column(&row.get::<_, String>(0)?),
+                    answer: row.get(1)?,
+                    correct: row.get(2)?,
+                    time_taken_ms: row.get(3)?,
+                    answered_at: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(attempts)
+    }
+}
+
+enum Order {
+    NewestFirst,
+    OldestFirst,
+}
+
+// The SELECT for a query, with its parameters
+fn select_reviews(query: &HistoryQuery, order: Order) -> (String, Vec<Box<dyn ToSql>>) {
+    let mut conditions: Vec<String> = Vec::new();
+    let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+    if let Some(since) = query.since {
+        conditions.push("created_at >= ?".to_string());
+        values.push(Box::new(since));
+    }
+    if let Some(until) = query.until {
+        conditions.push("created_at < ?".to_string());
+        values.push(Box::new(until));
+    }
+    if let Some(subject_id) = query.subject_id {
+        conditions.push("subject_id = ?".to_string());
+        values.push(Box::new(subject_id));
+    }
+    if let Some(subject_type) = &query.subject_type {
+        conditions.push("subject_type = ?".to_string());
+        values.push(Box::new(subject_type.clone()));
+    }
+    if !query.subject_types.is_empty() {
+        let placeholders = vec!["?"; query.subject_types.len()].join(", ");
+        conditions.push(format!("subject_type IN ({})", placeholders));
+        for subject_type in &query.subject_types {
+            values.push(Box::new(subject_type.clone()));
+        }
+    }
+    if query.incorrect_only {
+        conditions.push("(incorrect_meaning_answers > 0 OR incorrect_reading_answers > 0)".to_string());
+    }
+    if let Some(practice) = query.practice {
+        conditions.push("practice = ?".to_string());
+        values.push(Box::new(practice));
+    }
+
+    let mut sql = format!("SELECT {} FROM reviews", REVIEW_COLUMNS);
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(match order {
+        Order::NewestFirst => " ORDER BY created_at DESC, id DESC",
+        Order::OldestFirst => " ORDER BY created_at, id",
+    });
+    // A negative limit means no limit to SQLite
+    sql.push_str(" LIMIT ? OFFSET ?");
+    values.push(Box::new(query.limit.map(i64::from).unwrap_or(-1)));
+    values.push(Box::new(query.offset));
+
+    (sql, values)
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // A history in a fresh file under the system temp dir
+    fn test_history(name: &str) -> (History, PathBuf) {
+        let path = std::env::temp_dir().join(format!("wanipop-history-tests-{}.sqlite3", name));
+        let _ = fs::remove_file(&path);
+        (History::open_at(&path).unwrap(), path)
+    }
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, hour, 0, 0).unwrap()
+    }
+
+    fn entry(assignment_id: u64, subject_type: &str, created_at: DateTime<Utc>) -> HistoryEntry {
+        HistoryEntry {
+            assignment_id,
+            subject_id: assignment_id + 1000,
+            subject_type: Some(subject_type.to_string()),
+            characters: None,
+            starting_srs_stage: 1,
+            ending_srs_stage: 2,
+            incorrect_meaning_answers: 0,
+            incorrect_reading_answers: 0,
+            time_taken_ms: 0,
+            created_at,
+            attempts: Vec::new(),
+            practice: false,
+        }
+    }
+
+    fn assignment_ids(entries: &[HistoryEntry]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.assignment_id).collect()
+    }
+
+    #[test]
+    fn migrates_a_fresh_file() {
+        let (history, path) = test_history("migrate");
+        let version: usize = history.conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        drop(history);
+
+        // Opening again has nothing left to run
+        let history = History::open_at(&path).unwrap();
+        let version: usize = history.conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn skips_reviews_already_recorded() {
+        let (mut history, _) = test_history("dedup");
+        let first = entry(1, "kanji", at(10));
+
+        assert_eq!(history.record(&[first.clone(), entry(2, "kanji", at(10))]).unwrap(), 2);
+        // Same assignment and time is the same review, a later one is a new review
+        assert_eq!(history.record(&[first.clone(), entry(1, "kanji", at(11))]).unwrap(), 1);
+        assert_eq!(history.query(&HistoryQuery::default()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn filters_reviews() {
+        let (mut history, _) = test_history("filters");
+        let mut practice = entry(4, "vocabulary", at(13));
+        practice.practice = true;
+        history
+            .record(&[
+                entry(1, "radical", at(10)),
+                entry(2, "kanji", at(11)),
+                entry(3, "kana_vocabulary", at(12)),
+                practice,
+            ])
+            .unwrap();
+
+        let query = |query: HistoryQuery| assignment_ids(&history.query(&query).unwrap());
+
+        assert_eq!(query(HistoryQuery::default()), [4, 3, 2, 1]);
+        // since is inclusive, until isn't
+        assert_eq!(
+            query(HistoryQuery {
+                since: Some(at(11)),
+                until: Some(at(13)),
+                ..Default::default()
+            }),
+            [3, 2]
+        );
+        assert_eq!(
+            query(HistoryQuery {
+                subject_type: Some("kanji".to_string()),
+                ..Default::default()
+            }),
+            [2]
+        );
+        assert_eq!(
+            query(HistoryQuery {
+                subject_types: vec!["radical".to_string(), "kana_vocabulary".to_string()],
+                ..Default::default()
+            }),
+            [3, 1]
+        );
+        assert_eq!(
+            query(HistoryQuery {
+                practice: Some(true),
+                ..Default::default()
+            }),
+            [4]
+        );
+        assert_eq!(
+            query(HistoryQuery {
+                practice: Some(false),
+                limit: Some(2),
+                offset: 1,
+                ..Default::default()
+            }),
+            [2, 1]
+        );
+
+        let mut oldest_first = Vec::new();
+        history
+            .for_each(&HistoryQuery::default(), |entry| {
+                oldest_first.push(entry.assignment_id);
+                Ok::<(), rusqlite::Error>(())
+            })
+            .unwrap();
+        assert_eq!(oldest_first, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn keeps_attempts() {
+        let (mut history, _) = test_history("attempts");
+        let mut reviewed = entry(1, "kanji", at(10));
+        reviewed.attempts = vec![
+            Attempt {
+                question_type: QuestionType::Reading,
+                answer: "にち".to_string(),
+                correct: false,
+                time_taken_ms: 1500,
+                answered_at: at(10),
+            },
+            Attempt {
+                question_type: QuestionType::Meaning,
+                answer: "sun, day".to_string(),
+                correct: true,
+                time_taken_ms: 800,
+                answered_at: at(10) + chrono::Duration::seconds(5),
+            },
+        ];
+        history.record(&[reviewed.clone()]).unwrap();
+
+        let saved = history.query(&HistoryQuery::default()).unwrap();
+        let attempts = &saved[0].attempts;
+        assert_eq!(attempts.len(), 2);
+        for (saved, original) in attempts.iter().zip(&reviewed.attempts) {
+            assert_eq!(saved.question_type, original.question_type);
+            assert_eq!(saved.answer, original.answer);
+            assert_eq!(saved.correct, original.correct);
+            assert_eq!(saved.time_taken_ms, original.time_taken_ms);
+            assert_eq!(saved.answered_at, original.answered_at);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{fs, thread};
+use tauri::{async_runtime::spawn, AppHandle, Emitter, Manager};
+
+use crate::config::write_private_atomic;
+use crate::popup;
+
+// Sent by the running instance first, so a stale port taken by some other program is never mistaken for us
+const GREETING: &str = "wanipop";
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+// The primary may still be starting up when a second launch finds the lock taken
+const FORWARD_ATTEMPTS: u32 = 20;
+const FORWARD_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+// Held for as long as the primary runs. The OS lets go of it when the process exits,
+// crashes included, so it can never go stale.
+static INSTANCE_LOCK: OnceLock<File> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceCommand {
+    ReviewNow,
+    Settings,
+}
+
+impl InstanceCommand {
+    pub fn from_args(args: &[String]) -> Vec<InstanceCommand> {
+        args.iter()
+            .filter_map(|arg| match arg.as_str() {
+                "--review-now" => Some(InstanceCommand::ReviewNow),
+                "--settings" => Some(InstanceCommand::Settings),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+pub enum Instance {
+    // We're the only one running. Holds the listener other launches forward to, if it could be bound.
+    Primary(Option<TcpListener>),
+    // Another instance is running and has been handed our arguments
+    Secondary,
+}
+
+fn port_file_path() -> PathBuf {
+    let config_dir = dirs::config_dir().expect("Could not find config directory");
+    config_dir.join("wanipop").join("instance.port")
+}
+
+fn lock_file_path() -> PathBuf {
+    port_file_path().with_file_name("instance.lock")
+}
+
+enum Lock {
+    Acquired(File),
+    HeldElsewhere,
+}
+
+fn try_lock() -> io::Result<Lock> {
+    let path = lock_file_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().write(true).create(true).truncate(false).open(path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Lock::Acquired(file)),
+        Err(TryLockError::WouldBlock) => Ok(Lock::HeldElsewhere),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+// Whoever holds the lock is the primary, so two launches at the same moment can't both be
+pub fn acquire(args: &[String]) -> Instance {
+    match try_lock() {
+        Ok(Lock::Acquired(file)) => {
+            let _ = INSTANCE_LOCK.set(file);
+        }
+        Ok(Lock::HeldElsewhere) => {
+            for _ in 0..FORWARD_ATTEMPTS {
+                match forward_to_running(args) {
+                    Ok(true) => return Instance::Secondary,
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Could not reach running instance: {}", e),
+                }
+                thread::sleep(FORWARD_RETRY_DELAY);
+            }
+            // Still a secondary, a second scheduler is worse than a lost --review-now
+            eprintln!("WaniPOP is already running but didn't answer, giving up on {:?}", args);
+            return Instance::Secondary;
+        }
+        Err(e) => {
+            // Locking isn't supported here, fall back to checking for a live instance
+            eprintln!("Could not lock {}: {}", lock_file_path().display(), e);
+            match forward_to_running(args) {
+                Ok(true) => return Instance::Secondary,
+                Ok(false) => {}
+                Err(e) => eprintln!("Could not reach running instance: {}", e),
+            }
+        }
+    }
+
+    match bind() {
+        Ok(listener) => Instance::Primary(Some(listener)),
+        Err(e) => {
+            eprintln!("Could not set up single-instance listener: {}", e);
+            Instance::Primary(None)
+        }
+    }
+}
+
+fn forward_to_running(args: &[String]) -> io::Result<bool> {
+    let Ok(port) = fs::read_to_string(port_file_path()) else {
+        return Ok(false);
+    };
+    let Ok(port) = port.trim().parse::<u16>() else {
+        return Ok(false);
+    };
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) else {
+        return Ok(false);
+    };
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+
+    let mut greeting = String::new();
+    BufReader::new(&stream).read_line(&mut greeting)?;
+    if greeting.trim() != GREETING {
+        return Ok(false);
+    }
+
+    stream.write_all(args.join("\n").as_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+    Ok(true)
+}
+
+fn bind() -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    let path = port_file_path();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Written in one go, so a launch reading it never sees half a port number
+    write_private_atomic(&path, listener.local_addr()?.port().to_string().as_bytes())?;
+
+    Ok(listener)
+}
+
+// Accept arguments from later launches for as long as the app runs
+pub fn listen(app_handle: AppHandle, listener: TcpListener) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+
+            let mut args = String::new();
+            let received = stream
+                .write_all(format!("{}\n", GREETING).as_bytes())
+                .and_then(|_| stream.set_read_timeout(Some(CONNECT_TIMEOUT)))
+                .and_then(|_| stream.read_to_string(&mut args));
+
+            if let Err(e) = received {
+                eprintln!("Error receiving arguments from new instance: {}", e);
+                continue;
+            }
+
+            let args: Vec<String> = args.lines().map(String::from).collect();
+            println!("Another instance was launched with {:?}", args);
+            handle_commands(&app_handle, &InstanceCommand::from_args(&args));
+        }
+    });
+}
+
+pub fn handle_commands(app_handle: &AppHandle, commands: &[InstanceCommand]) {
+    for command in commands {
+        match command {
+            InstanceCommand::ReviewNow => {
+                let app_handle = app_handle.clone();
+                spawn(async move { popup::open_review_session(&app_handle).await });
+            }
+            InstanceCommand::Settings => {
+                if let Some(win) = app_handle.get_webview_window("main") {
+                    let _ = win.show();
+                    let _ = win.set_focus();
+                    let _ = app_handle.emit("open-settings", ());
+                }
+            }
+        }
+    }
+}
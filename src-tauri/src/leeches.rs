@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
+use reqwest::Client;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::write_private_atomic;
+use crate::profiles;
+use crate::wanikani::{self, ReviewStatisticData};
+
+// Subjects scoring at least this are treated as leeches
+pub const LEECH_THRESHOLD: f64 = 1.0;
+
+#[derive(Serialize, Debug)]
+pub struct Leech {
+    pub subject_id: u64,
+    pub subject_type: String,
+    pub characters: Option<String>,
+    pub meaning: Option<String>,
+    pub score: f64,
+    pub meaning_incorrect: u32,
+    pub reading_incorrect: u32,
+    pub percentage_correct: u8,
+}
+
+// How often a subject has been missed, weighed down by how many times in a row it's been
+// right since. Meaning and reading are scored separately and the worse one counts, so an
+// item that's only ever wrong on its reading still shows up.
+pub fn leech_score(stats: &ReviewStatisticData) -> f64 {
+    let score = |incorrect: u32, streak: u32| incorrect as f64 / (streak.max(1) as f64).powf(1.5);
+
+    score(stats.meaning_incorrect, stats.meaning_current_streak)
+        .max(score(stats.reading_incorrect, stats.reading_current_streak))
+}
+
+// Leech scores by subject, only for subjects over the threshold. Hidden subjects are
+// no longer reviewed, so they never count.
+pub fn leech_scores<'a>(stats: impl IntoIterator<Item = &'a ReviewStatisticData>) -> HashMap<u64, f64> {
+    stats
+        .into_iter()
+        .filter(|stats| !stats.hidden)
+        .map(|stats| (stats.subject_id, leech_score(stats)))
+        .filter(|(_, score)| *score >= LEECH_THRESHOLD)
+        .collect()
+}
+
+// The worst leeches first
+pub fn ranked(scores: &HashMap<u64, f64>) -> Vec<(u64, f64)> {
+    let mut ranked: Vec<(u64, f64)> = scores.iter().map(|(id, score)| (*id, *score)).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+// Pick a batch from the due subjects, leeches first and the rest at random
+pub fn leeches_first(due: Vec<u64>, scores: &HashMap<u64, f64>, batch_size: usize) -> Vec<u64> {
+    let (leeches, mut others): (Vec<u64>, Vec<u64>) =
+        due.into_iter().partition(|id| scores.contains_key(id));
+
+    let mut leeches: Vec<(u64, f64)> = leeches.into_iter().map(|id| (id, scores[&id])).collect();
+    leeches.sort_by(|a, b| b.1.total_cmp(&a.1));
+    others.shuffle(&mut rand::rng());
+
+    leeches
+        .into_iter()
+        .map(|(id, _)| id)
+        .chain(others)
+        .take(batch_size)
+        .collect()
+}
+
+// Every review statistic downloaded so far, by subject. After the first download only
+// the ones updated since are fetched, the whole collection is thousands of items.
+#[derive(Serialize, Deserialize)]
+struct CachedStatistics {
+    // Which token they were fetched with, another account's are never used
+    key_digest: String,
+    fetched_at: DateTime<Utc>,
+    statistics: HashMap<u64, ReviewStatisticData>,
+}
+
+impl CachedStatistics {
+    fn path(profile: &str) -> PathBuf {
+        profiles::profile_dir(profile).join("cache").join("review_statistics.json")
+    }
+
+    fn key_digest(api_key: &str) -> String {
+        digest::digest(&digest::SHA256, api_key.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn load(profile: &str, api_key: &str) -> Option<CachedStatistics> {
+        let data = fs::read_to_string(Self::path(profile)).ok()?;
+        let cached: CachedStatistics = serde_json::from_str(&data).ok()?;
+        (cached.key_digest == Self::key_digest(api_key)).then_some(cached)
+    }
+
+    fn save(&self, profile: &str) -> std::io::Result<()> {
+        let path = Self::path(profile);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self)?;
+        write_private_atomic(&path, json.as_bytes())
+    }
+}
+
+// Review statistics for every subject the user has reviewed, brought up to date from the
+// cache. The cached ones are used as they are when WaniKani can't be reached.
+pub async fn review_statistics(
+    client: &Client,
+    api_key: String,
+    profile: &str,
+) -> Result<HashMap<u64, ReviewStatisticData>, String> {
+    let cached = CachedStatistics::load(profile, &api_key);
+    let fetched_at = Utc::now();
+
+    let updated_after = cached.as_ref().map(|cached| cached.fetched_at);
+    let updated = match wanikani::fetch_review_statistics(client, api_key.clone(), updated_after).await {
+        Ok(updated) => updated,
+        Err(e) => match cached {
+            Some(cached) => {
+                eprintln!("Could not update review statistics, using the cached ones: {}", e);
+                return Ok(cached.statistics);
+            }
+            None => return Err(e.to_string()),
+        },
+    };
+
+    let mut statistics = cached.map(|cached| cached.statistics).unwrap_or_default();
+    statistics.extend(updated.into_iter().map(|s| (s.data.subject_id, s.data)));
+    let fresh = CachedStatistics {
+        key_digest: CachedStatistics::key_digest(&api_key),
+        fetched_at,
+        statistics,
+    };
+    if let Err(e) = fresh.save(profile) {
+        eprintln!("Could not cache review statistics: {}", e);
+    }
+    Ok(fresh.statistics)
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use crate::config::write_private_atomic;
+use crate::popup::notify;
+use crate::profiles;
+use crate::wanikani::{AccountStatus, LevelProgression};
+use crate::AppState;
+
+// The level WaniPOP last saw, so a level-up is only celebrated once
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KnownLevel {
+    pub level: u32,
+    pub seen_at: DateTime<Utc>,
+}
+
+impl KnownLevel {
+    fn path(profile: &str) -> PathBuf {
+        profiles::profile_dir(profile).join("level.json")
+    }
+
+    pub fn load(profile: &str) -> Option<KnownLevel> {
+        let data = fs::read_to_string(Self::path(profile)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub fn save(&self, profile: &str) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        write_private_atomic(&Self::path(profile), json.as_bytes())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct LevelTime {
+    pub level: u32,
+    pub unlocked_at: Option<DateTime<Utc>>,
+    pub passed_at: Option<DateTime<Utc>>,
+    // From unlock to passing, or to now for the level in progress
+    pub duration_in_seconds: Option<i64>,
+    pub in_progress: bool,
+}
+
+// How long each level took. Progressions abandoned by a reset are left out.
+pub fn level_times(progressions: &[LevelProgression], now: DateTime<Utc>) -> Vec<LevelTime> {
+    let mut times: Vec<LevelTime> = progressions
+        .iter()
+        .map(|p| &p.data)
+        .filter(|p| p.abandoned_at.is_none())
+        .map(|p| {
+            let end = p.passed_at.unwrap_or(now);
+            LevelTime {
+                level: p.level,
+                unlocked_at: p.unlocked_at,
+                passed_at: p.passed_at,
+                duration_in_seconds: p.unlocked_at.map(|unlocked| (end - unlocked).num_seconds()),
+                in_progress: p.passed_at.is_none(),
+            }
+        })
+        .collect();
+    times.sort_by_key(|t| t.level);
+    times
+}
+
+// Compare the user's level with the last one seen, and celebrate if it went up.
+// Called by the tray refresh with the account it just fetched.
+pub fn check_level(app_handle: &AppHandle, account: &AccountStatus) {
+    let profile = app_handle.state::<AppState>().config.lock().unwrap().profile.clone();
+
+    // Nothing to celebrate the first time, or after a reset
+    let known = KnownLevel::load(&profile);
+    if known.as_ref().is_some_and(|known| known.level == account.level) {
+        return;
+    }
+    if known.is_some_and(|known| account.level > known.level) {
+        notify(
+            app_handle,
+            &format!("🎉 Level up! You're now level {}! 🎉\nNew lessons are waiting for you.", account.level),
+        );
+    }
+
+    let known = KnownLevel {
+        level: account.level,
+        seen_at: Utc::now(),
+    };
+    if let Err(e) = known.save(&profile) {
+        eprintln!("Could not save level: {}", e);
+    }
+}
@@ -1,22 +1,68 @@
+mod autostart;
+mod bundle;
 mod config;
+mod config_watch;
 mod commands;
+mod export;
+mod forecast;
+mod goal;
+mod history;
+mod instance;
+mod leeches;
+mod levels;
+mod overrides;
+mod popup;
+mod practice;
+mod profiles;
+mod scheduler;
+mod secrets;
+mod srs;
+mod stats;
+mod tray;
 mod wanikani;
 
 use std::sync::{Arc, Mutex};
 use reqwest::Client;
-use config::WanipopConfig;
+use config::{ConfigWarning, WanipopConfig};
+use practice::PracticeSource;
 use commands::*;
+use scheduler::{Scheduler, SystemClock, Tick};
 use serde::Serialize;
+use wanikani::{AccountStatus, UserData};
 
 use std::time::Duration;
 use tokio::time::sleep;
 use tauri::{
-    async_runtime::spawn, menu::{Menu, MenuItem}, tray::TrayIconBuilder, AppHandle, Emitter, LogicalSize, Manager, PhysicalSize, Size, WebviewWindowBuilder, WindowEvent
+    async_runtime::spawn, tray::TrayIconBuilder, AppHandle, LogicalSize, Manager, PhysicalSize, Size, WebviewWindowBuilder, WindowEvent
 };
 
 pub struct AppState {
     pub http_client: Client,
     pub config: Arc<Mutex<WanipopConfig>>,
+    // Set when the config on disk couldn't be loaded and the defaults are in use
+    pub config_warning: Option<ConfigWarning>,
+    // What the tray last heard about the account, see account_status()
+    pub account: Mutex<Option<AccountStatus>>,
+}
+
+impl AppState {
+    // The account status the tray keeps up to date. WaniKani is only asked when there's
+    // none for this key yet, e.g. right after startup or a new key.
+    pub async fn account_status(&self, api_key: &str) -> Result<AccountStatus, reqwest::Error> {
+        let cached = self.account.lock().unwrap().clone();
+        if let Some(status) = cached.filter(|status| status.api_key == api_key) {
+            return Ok(status);
+        }
+
+        let user = wanikani::fetch_user(&self.http_client, api_key.to_string()).await?;
+        Ok(self.remember_account(api_key, &user))
+    }
+
+    pub fn remember_account(&self, api_key: &str, user: &UserData) -> AccountStatus {
+        let status = AccountStatus::new(api_key, user);
+        *self.account.lock().unwrap() = Some(status.clone());
+        status
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -24,11 +70,73 @@ pub struct ReviewPayload {
     pub payload: Vec<ReviewCard>
 }
 
+fn popup_timings(app_handle: &AppHandle) -> (Duration, Duration) {
+    let config = app_handle.state::<AppState>().config.lock().unwrap().clone();
+    let interval = Duration::from_secs((config.time_between_popups_in_minutes * 60) as u64);
+    let grace_period = Duration::from_secs(config.resume_grace_period_in_seconds as u64);
+    (interval, grace_period)
+}
+
 pub fn run() {
-    let config = WanipopConfig::load_or_create().expect("Failed to load or create config");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let launch_options = match overrides::LaunchOptions::from_args(&args) {
+        Ok(launch_options) => launch_options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let profiles = profiles::Profiles::load();
+    if let Some(path) = launch_options.config_path {
+        WanipopConfig::override_config_path(&profiles.active, path);
+    }
+
+    if launch_options.print_config {
+        let (mut config, warning) = WanipopConfig::read(&profiles.active);
+        if let Some(warning) = warning {
+            eprintln!("{}", warning);
+        }
+        overrides::apply_env(&mut config);
+        overrides::print_config(&config);
+        return;
+    }
+
+    if let Some(path) = launch_options.export_path {
+        let (config, _) = WanipopConfig::read(&profiles.active);
+        match export::export_to_path(&config.profile, &launch_options.export_options, &path) {
+            Ok(summary) => println!(
+                "Exported {} rows to {} (schema version {})",
+                summary.rows,
+                path.display(),
+                summary.schema_version
+            ),
+            Err(e) => eprintln!("{}", e),
+        }
+        return;
+    }
+
+    let listener = match instance::acquire(&args) {
+        instance::Instance::Primary(listener) => listener,
+        instance::Instance::Secondary => {
+            println!("WaniPOP is already running, handed over {:?}", args);
+            return;
+        }
+    };
+
+    let (mut config, config_warning) = WanipopConfig::load_or_create(&profiles.active);
+    overrides::apply_env(&mut config);
+    if let Err(e) = autostart::sync_config(&mut config) {
+        eprintln!("Could not check autostart entry: {}", e);
+    }
     let config_copy = config.clone();
     let http_client = Client::new();
-    let state = AppState { config: Arc::new(Mutex::new(config)), http_client };
+    let state = AppState {
+        config: Arc::new(Mutex::new(config)),
+        http_client,
+        config_warning: config_warning.clone(),
+        account: Mutex::new(None),
+    };
 
     tauri::Builder::default()
         .on_window_event(|window, event| {
@@ -41,81 +149,91 @@ pub fn run() {
             }
         })
         .setup(move |app| {
-            use tauri_plugin_notification::NotificationExt;
+            if let Some(warning) = &config_warning {
+                use tauri_plugin_notification::NotificationExt;
+                let _ = app.notification()
+                    .builder()
+                    .title(&warning.title)
+                    .body(&warning.message)
+                    .show();
+            }
 
             //Set window decorations
             let win = app.get_webview_window("main").unwrap();
             let _ = win.set_decorations(!config_copy.hide_window_decorations);
 
             //Menu
-            let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let tray_menu = Menu::with_items(app, &[&quit])?;
+            let tray_menu = tray::build_menu(app.handle())?;
 
             //Create system tray icon
-            let _ = TrayIconBuilder::new()
+            let tray_icon = app.default_window_icon().unwrap().clone().to_owned();
+            let _ = TrayIconBuilder::with_id(tray::TRAY_ID)
+                .icon(tray_icon.clone())
+                .tooltip("WaniPOP!")
                 .menu(&tray_menu)
                 .on_menu_event(|app, event| match event.id.as_ref() {
+                  "review_now" => {
+                    let app = app.clone();
+                    spawn(async move { popup::open_review_session(&app).await });
+                  }
+                  "practice_mistakes" => {
+                    let app = app.clone();
+                    let source = PracticeSource::RecentMistakes { days: practice::DEFAULT_MISTAKE_DAYS };
+                    spawn(async move { popup::open_practice_session(&app, source).await });
+                  }
+                  "practice_burned" => {
+                    let app = app.clone();
+                    spawn(async move { popup::open_practice_session(&app, PracticeSource::Burned).await });
+                  }
                   "quit" => {
                     println!("quit menu item was clicked");
                     app.exit(0);
                   }
+                  id if id.starts_with(tray::PROFILE_MENU_PREFIX) => {
+                    let profile = &id[tray::PROFILE_MENU_PREFIX.len()..];
+                    if let Err(e) = activate_profile(app, profile) {
+                      eprintln!("Could not switch to profile {}: {}", profile, e);
+                    }
+                  }
                   _ => {
                     println!("menu item {:?} not handled", event.id);
                   }
                 })
                 .build(app)?;
 
-            //Set up timer to pop up after interval
-            let minutes = config_copy.time_between_popups_in_minutes;
-            let interval = Duration::from_secs((minutes * 60) as u64);
+            //Reload the config when it's edited outside the app
+            spawn(config_watch::watch_loop(app.handle().clone()));
 
+            //Keep the tray tooltip and badge up to date
+            spawn(tray::refresh_loop(app.handle().clone(), tray_icon));
+
+            //Take commands from later launches, and handle the ones we were started with
+            if let Some(listener) = listener {
+                instance::listen(app.handle().clone(), listener);
+            }
+            instance::handle_commands(app.handle(), &instance::InstanceCommand::from_args(&args));
+
+            //Set up timer to pop up after interval
             let app_handle: AppHandle = app.handle().clone();
             spawn(async move {
+                let clock = SystemClock::new();
+                let (interval, grace_period) = popup_timings(&app_handle);
+                let mut scheduler = Scheduler::new(&clock, interval, grace_period);
+                // Shorter than the usual interval while behind on a daily goal late in the day
+                let mut catch_up_interval: Option<Duration> = None;
+
                 loop {
-                    // wait
-                    sleep(interval).await;
-
-                    //Check if window is still open before seeing if reviews are available
-                    if let Some(win) = app_handle.get_webview_window("main") {
-                        if let Ok(is_visible) = win.is_visible() {
-                            if is_visible {
-                                println!("Window is still open. Doing nothing.");
-                                continue;
-                            }
-
-                            let reviews = get_review_batch(app_handle.state::<AppState>()).await;
-                            println!("Reviews: {:#?}", reviews);
-
-                            match reviews {
-                                Err(err) => {
-                                    if err == "No reviews available right now".to_string() {
-                                        let noti = app_handle.notification()
-                                            .builder()
-                                            .title("WaniPOP!")
-                                            .body("Just checked, and you're all caught up on reviews! 🥳\nGreat job staying on top of things! 🎉")
-                                            .show();
-                                        println!("Notification: {:#?}", noti);
-                                    }
-                                    eprintln!("Error fetching reviews: {}", err);
-                                    continue;
-                                }
-                                Ok(reviews) => {
-                                    if reviews.len() > 0 {
-                                        // Reopen window and reinitialize if it's not already open
-                                        let _ = win.show();
-                                        let _ = win.set_focus();
-                                        let _ = app_handle.emit("reset-session", reviews);
-                                    } else {
-                                        let noti = app_handle.notification()
-                                            .builder()
-                                            .title("WaniPOP!")
-                                            .body("Just checked, and you're all caught up on reviews! 🥳\nGreat job staying on top of things! 🎉")
-                                            .show();
-                                        println!("Notification: {:#?}", noti);
-                                        println!("No reviews available right now");
-                                    }
-                                }
-                            }
+                    // Pick up changes made in the settings since the last tick
+                    let (interval, grace_period) = popup_timings(&app_handle);
+                    let interval = catch_up_interval.map_or(interval, |catch_up| catch_up.min(interval));
+                    scheduler.set_interval(interval);
+                    scheduler.set_grace_period(grace_period);
+
+                    match scheduler.next(&clock) {
+                        Tick::Wait(duration) => sleep(duration).await,
+                        Tick::Check => {
+                            popup::check_for_popup(&app_handle).await;
+                            catch_up_interval = goal::catch_up_interval(&app_handle).await;
                         }
                     }
                 }
@@ -129,15 +247,36 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Config
             get_config,
+            get_config_warning,
             set_api_key,
-            set_num_of_reviews_per_batch,
-            set_time_between_popups_in_minutes,
-            set_hide_window_decorations,
+            update_config,
+            set_autostart,
+            set_secret_store,
+            // Settings bundles
+            export_settings,
+            preview_import,
+            import_settings,
+            // Profiles
+            list_profiles,
+            create_profile,
+            delete_profile,
+            switch_profile,
             // User
             get_wanikani_user,
+            get_level_history,
             // Reviews
             get_review_batch,
             submit_review_batch,
+            get_top_leeches,
+            get_forecast,
+            // Practice
+            get_practice_batch,
+            submit_practice_batch,
+            // History
+            get_review_history,
+            get_stats,
+            get_goal_progress,
+            export_history,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
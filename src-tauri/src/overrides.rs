@@ -0,0 +1,146 @@
+// Settings that can be overridden at launch, for testing and dotfile-managed setups.
+//
+// Where the config file is read from, first match wins:
+//   1. --config <path>
+//   2. WANIPOP_CONFIG
+//   3. the active profile's config.json
+//
+// Values, first match wins:
+//   1. WANIPOP_API_KEY for the token, WANIPOP_<FIELD> for any other setting,
+//      e.g. WANIPOP_NUM_OF_REVIEWS_PER_BATCH=10 or WANIPOP_POPUP_BEHAVIOR=notify_only
+//   2. the config file (and the secret store for the token)
+//   3. the defaults
+//
+// Overrides only apply to the profile active at launch, and are never written back to disk.
+
+use serde_json::{Map, Value};
+use std::env;
+use std::path::PathBuf;
+
+use crate::config::{is_patchable, WanipopConfig};
+use crate::export::{ExportData, ExportFormat, ExportOptions};
+
+#[derive(Debug, Default)]
+pub struct LaunchOptions {
+    pub config_path: Option<PathBuf>,
+    pub print_config: bool,
+    // --export <path> and the flags that go with it, see export.rs
+    pub export_path: Option<PathBuf>,
+    pub export_options: ExportOptions,
+}
+
+fn parse_date(flag: &str, value: Option<&String>) -> Result<chrono::NaiveDate, String> {
+    let value = value.ok_or(format!("{} needs a date", flag))?;
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("{} needs a date like 2025-01-31, got {}", flag, value))
+}
+
+impl LaunchOptions {
+    pub fn from_args(args: &[String]) -> Result<LaunchOptions, String> {
+        let mut options = LaunchOptions::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    let path = args.next().ok_or("--config needs a path")?;
+                    options.config_path = Some(PathBuf::from(path));
+                }
+                "--print-config" => options.print_config = true,
+                "--export" => {
+                    let path = args.next().ok_or("--export needs a path")?;
+                    options.export_path = Some(PathBuf::from(path));
+                }
+                "--data" => {
+                    let value = args.next().ok_or("--data needs reviews, attempts or daily_stats")?;
+                    options.export_options.data = ExportData::parse(value)
+                        .ok_or(format!("--data needs reviews, attempts or daily_stats, got {}", value))?;
+                }
+                "--format" => {
+                    let value = args.next().ok_or("--format needs csv or jsonl")?;
+                    options.export_options.format = Some(
+                        ExportFormat::parse(value).ok_or(format!("--format needs csv or jsonl, got {}", value))?,
+                    );
+                }
+                "--since" => options.export_options.since = Some(parse_date("--since", args.next())?),
+                "--until" => options.export_options.until = Some(parse_date("--until", args.next())?),
+                "--subject-type" => {
+                    let value = args.next().ok_or("--subject-type needs a type, e.g. kanji")?;
+                    options
+                        .export_options
+                        .subject_types
+                        .extend(value.split(',').map(|t| t.trim().to_string()));
+                }
+                "--include-practice" => options.export_options.include_practice = true,
+                _ => {
+                    if let Some(path) = arg.strip_prefix("--config=") {
+                        options.config_path = Some(PathBuf::from(path));
+                    }
+                }
+            }
+        }
+
+        if options.config_path.is_none() {
+            options.config_path = env::var_os("WANIPOP_CONFIG").map(PathBuf::from);
+        }
+        Ok(options)
+    }
+}
+
+fn env_var_name(field: &str) -> String {
+    format!("WANIPOP_{}", field.to_uppercase())
+}
+
+// Apply WANIPOP_* variables on top of a loaded config. Bad values are reported and skipped.
+pub fn apply_env(config: &mut WanipopConfig) {
+    if let Ok(key) = env::var("WANIPOP_API_KEY") {
+        config.wanikani_api_key = Some(key);
+        config.overrides.push("wanikani_api_key".to_string());
+    }
+
+    let Ok(Value::Object(fields)) = serde_json::to_value(&*config) else {
+        return;
+    };
+
+    for field in fields.keys() {
+        if !is_patchable(field) {
+            continue;
+        }
+        let name = env_var_name(field);
+        let Ok(raw) = env::var(&name) else {
+            continue;
+        };
+
+        // Numbers and booleans parse as JSON, anything else is taken as a plain string
+        let value = serde_json::from_str(&raw).unwrap_or(Value::String(raw));
+        let mut patch = Map::new();
+        patch.insert(field.clone(), value);
+
+        match config.patched(&Value::Object(patch)) {
+            Ok(patched) => {
+                *config = patched;
+                config.overrides.push(field.clone());
+            }
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("Ignoring {}: {}", name, error.message);
+                }
+            }
+        }
+    }
+}
+
+// For --print-config: the merged result, where it came from, and what was overridden
+pub fn print_config(config: &WanipopConfig) {
+    let mut shown = serde_json::to_value(config.masked()).unwrap_or(Value::Null);
+    if config.wanikani_api_key.is_some() {
+        shown["wanikani_api_key"] = Value::from("<redacted>");
+    }
+
+    println!("# profile: {}", config.profile);
+    println!("# file: {}", WanipopConfig::config_path(&config.profile).display());
+    if !config.overrides.is_empty() {
+        println!("# overridden from the environment: {}", config.overrides.join(", "));
+    }
+    println!("{}", serde_json::to_string_pretty(&shown).unwrap_or_default());
+}
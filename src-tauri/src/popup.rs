@@ -0,0 +1,151 @@
+use crate::commands::{get_practice_batch, get_review_batch};
+use crate::config::PopupBehavior;
+use crate::practice::PracticeSource;
+use crate::tray;
+use crate::wanikani;
+use crate::AppState;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+pub fn notify(app_handle: &AppHandle, body: &str) {
+    let noti = app_handle
+        .notification()
+        .builder()
+        .title("WaniPOP!")
+        .body(body)
+        .show();
+    println!("Notification: {:#?}", noti);
+}
+
+fn notify_caught_up(app_handle: &AppHandle) {
+    notify(
+        app_handle,
+        "Just checked, and you're all caught up on reviews! 🥳\nGreat job staying on top of things! 🎉",
+    );
+}
+
+// Vacation mode or a lapsed subscription, None if WaniKani couldn't be asked
+async fn pause_reason(app_handle: &AppHandle) -> Option<wanikani::PauseReason> {
+    let state = app_handle.state::<AppState>();
+    let api_key = state.config.lock().unwrap().wanikani_api_key.clone()?;
+    state.account_status(&api_key).await.ok()?.pause_reason
+}
+
+async fn pending_review_count(app_handle: &AppHandle) -> Option<usize> {
+    let (client, api_key) = {
+        let state = app_handle.state::<AppState>();
+        let cfg = state.config.lock().unwrap();
+        (state.http_client.clone(), cfg.wanikani_api_key.clone()?)
+    };
+
+    let summary = wanikani::fetch_summary(&client, api_key).await.ok()?;
+    Some(tray::available_count(&summary.reviews, chrono::Utc::now()))
+}
+
+// Called by the scheduler when it's time to check in on the user
+pub async fn check_for_popup(app_handle: &AppHandle) {
+    //Check if window is still open before seeing if reviews are available
+    if let Some(win) = app_handle.get_webview_window("main") {
+        if let Ok(is_visible) = win.is_visible() {
+            if is_visible {
+                println!("Window is still open. Doing nothing.");
+                return;
+            }
+
+            // The tray already says why
+            if let Some(reason) = pause_reason(app_handle).await {
+                println!("{}. Not popping up.", reason.message());
+                return;
+            }
+
+            let behavior = app_handle
+                .state::<AppState>()
+                .config
+                .lock()
+                .unwrap()
+                .popup_behavior;
+
+            // Only the count is needed, the batch is fetched when the user opens the session
+            if behavior == PopupBehavior::NotifyOnly {
+                match pending_review_count(app_handle).await {
+                    Some(0) => notify_caught_up(app_handle),
+                    Some(count) => notify(
+                        app_handle,
+                        &format!(
+                            "You have {} reviews waiting! 📝\nPick \"Start Reviews\" from the tray when you're ready.",
+                            count
+                        ),
+                    ),
+                    None => eprintln!("Could not check for reviews"),
+                }
+                return;
+            }
+
+            let reviews = get_review_batch(app_handle.state::<AppState>()).await;
+            println!("Reviews: {:#?}", reviews);
+
+            match reviews {
+                Err(err) => {
+                    if err == "No reviews available right now".to_string() {
+                        notify_caught_up(app_handle);
+                    }
+                    eprintln!("Error fetching reviews: {}", err);
+                }
+                Ok(reviews) => {
+                    if reviews.is_empty() {
+                        notify_caught_up(app_handle);
+                        println!("No reviews available right now");
+                        return;
+                    }
+
+                    // Reopen window and reinitialize if it's not already open
+                    let _ = win.show();
+                    let _ = win.set_focus();
+                    let _ = app_handle.emit("reset-session", reviews);
+                }
+            }
+        }
+    }
+}
+
+// Fetch a fresh batch and bring the window up, no matter the popup behavior.
+// Used by the tray and anything else that starts a session on the user's request.
+pub async fn open_review_session(app_handle: &AppHandle) {
+    let Some(win) = app_handle.get_webview_window("main") else {
+        return;
+    };
+
+    match get_review_batch(app_handle.state::<AppState>()).await {
+        Ok(reviews) => {
+            let _ = win.show();
+            let _ = win.set_focus();
+            let _ = app_handle.emit("reset-session", reviews);
+        }
+        Err(err) => {
+            // Let the window explain what went wrong
+            eprintln!("Error fetching reviews: {}", err);
+            let _ = win.show();
+            let _ = win.set_focus();
+            let _ = app_handle.emit("reset-session", ());
+        }
+    }
+}
+
+pub async fn open_practice_session(app_handle: &AppHandle, source: PracticeSource) {
+    let Some(win) = app_handle.get_webview_window("main") else {
+        return;
+    };
+
+    match get_practice_batch(app_handle.state::<AppState>(), source).await {
+        Ok(cards) => {
+            let _ = win.show();
+            let _ = win.set_focus();
+            let _ = app_handle.emit("practice-session", cards);
+        }
+        Err(err) => {
+            // No window to fall back on here, an empty practice session isn't worth opening
+            eprintln!("Error building practice session: {}", err);
+            notify(app_handle, &format!("Couldn't start practice: {}", err));
+        }
+    }
+}
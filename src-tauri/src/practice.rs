@@ -0,0 +1,145 @@
+// Practice sessions, for drilling without touching SRS. The cards are built the same way
+// as for reviews, but answers only go into the local history, never to WaniKani.
+
+use chrono::{Duration, Utc};
+use rand::seq::SliceRandom;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::commands::{review_cards, ReviewCard};
+use crate::history::{Attempt, History, HistoryEntry, HistoryQuery};
+use crate::wanikani::{self, SubmittedReviewData};
+
+pub const DEFAULT_MISTAKE_DAYS: u32 = 14;
+
+fn default_mistake_days() -> u32 {
+    DEFAULT_MISTAKE_DAYS
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PracticeSource {
+    // Subjects missed in the last few days, according to the local history
+    RecentMistakes {
+        #[serde(default = "default_mistake_days")]
+        days: u32,
+    },
+    Burned,
+    // Started items from one level
+    Level { level: u8 },
+}
+
+// A graded practice card, sent back by the quiz instead of going through submit_review
+#[derive(Deserialize, Debug)]
+pub struct PracticeResult {
+    pub assignment_id: u64,
+    pub subject_id: u64,
+    pub subject_type: String,
+    pub characters: Option<String>,
+    pub srs_stage: u8,
+    pub incorrect_meaning_answers: u8,
+    pub incorrect_reading_answers: u8,
+    #[serde(default)]
+    pub attempts: Vec<Attempt>,
+}
+
+impl PracticeResult {
+    // Shaped like a submitted review so the results screen can show it, with the stage unchanged
+    pub fn to_review_data(&self) -> SubmittedReviewData {
+        SubmittedReviewData {
+            created_at: Utc::now(),
+            assignment_id: self.assignment_id,
+            subject_id: self.subject_id,
+            starting_srs_stage: self.srs_stage,
+            ending_srs_stage: self.srs_stage,
+            incorrect_meaning_answers: self.incorrect_meaning_answers,
+            incorrect_reading_answers: self.incorrect_reading_answers,
+            starting_srs_stage_name: None,
+            ending_srs_stage_name: None,
+            next_review_at: None,
+            passed: None,
+            spaced_repetition_system_id: None,
+        }
+    }
+
+    pub fn to_history_entry(&self, review: &SubmittedReviewData) -> HistoryEntry {
+        HistoryEntry {
+            subject_type: Some(self.subject_type.clone()),
+            characters: self.characters.clone(),
+            time_taken_ms: self.attempts.iter().map(|a| a.time_taken_ms).sum(),
+            attempts: self.attempts.clone(),
+            practice: true,
+            ..HistoryEntry::new(review, None)
+        }
+    }
+}
+
+// Subjects answered wrong at least once in the last `days` days, most recent first
+fn recent_mistakes(profile: &str, days: u32) -> Result<Vec<u64>, String> {
+    let history = History::open(profile).map_err(|e| e.to_string())?;
+    let entries = history
+        .query(&HistoryQuery {
+            since: Some(Utc::now() - Duration::days(days as i64)),
+            incorrect_only: true,
+            // Only real reviews, otherwise a slip during practice would keep a subject coming back
+            practice: Some(false),
+            ..Default::default()
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut subject_ids: Vec<u64> = Vec::new();
+    for entry in entries {
+        if !subject_ids.contains(&entry.subject_id) {
+            subject_ids.push(entry.subject_id);
+        }
+    }
+    Ok(subject_ids)
+}
+
+pub async fn build_batch(
+    client: &Client,
+    api_key: String,
+    max_level_granted: u8,
+    profile: &str,
+    source: PracticeSource,
+    batch_size: usize,
+) -> Result<Vec<ReviewCard>, String> {
+    let granted = wanikani::granted_levels(max_level_granted);
+
+    let mut assignments = match source {
+        PracticeSource::RecentMistakes { days } => {
+            let mut ids = recent_mistakes(profile, days)?;
+            ids.shuffle(&mut rand::rng());
+            ids.truncate(batch_size);
+            if ids.is_empty() {
+                return Err(format!("No mistakes in the last {} days, nice!", days));
+            }
+            wanikani::fetch_assignments_for_subjects(client, api_key.clone(), &ids, max_level_granted).await
+        }
+        PracticeSource::Burned => {
+            let filters = format!("burned=true&{}", granted);
+            wanikani::fetch_assignments(client, api_key.clone(), &filters).await
+        }
+        PracticeSource::Level { level } => {
+            if level > max_level_granted {
+                return Err(format!("Your subscription only covers levels 1-{}", max_level_granted));
+            }
+            let filters = format!("levels={}&started=true", level);
+            wanikani::fetch_assignments(client, api_key.clone(), &filters).await
+        }
+    }
+    .map_err(|e| format!("Assignments error: {}", e))?;
+
+    assignments.shuffle(&mut rand::rng());
+    assignments.truncate(batch_size);
+    if assignments.is_empty() {
+        return Err("Nothing to practice from there yet".to_string());
+    }
+
+    let ids: Vec<u64> = assignments.iter().map(|a| a.data.subject_id).collect();
+    let subjects = wanikani::fetch_subjects(client, api_key, &ids, max_level_granted)
+        .await
+        .map_err(|e| format!("Subjects error: {}", e))?;
+
+    Ok(review_cards(assignments, &subjects))
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::{fs, io};
+
+use crate::config::write_private_atomic;
+
+// The profile that existed before there were profiles. Its data stays where it always
+// was, directly in the wanipop config directory, so nothing has to move on upgrade.
+pub const DEFAULT_PROFILE: &str = "default";
+
+pub fn base_dir() -> PathBuf {
+    let config_dir = dirs::config_dir().expect("Could not find config directory");
+    config_dir.join("wanipop")
+}
+
+// Everything that belongs to one account lives in here: config, caches, history
+pub fn profile_dir(profile: &str) -> PathBuf {
+    if profile == DEFAULT_PROFILE {
+        base_dir()
+    } else {
+        base_dir().join("profiles").join(profile)
+    }
+}
+
+// Names end up in paths and keyring entries, so keep them boring
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 32 {
+        return Err("Profile names must be 1 to 32 characters long".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Profile names may only use letters, numbers, - and _".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Profiles {
+    pub active: String,
+    pub names: Vec<String>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Profiles {
+            active: DEFAULT_PROFILE.to_string(),
+            names: vec![DEFAULT_PROFILE.to_string()],
+        }
+    }
+}
+
+impl Profiles {
+    fn path() -> PathBuf {
+        base_dir().join("profiles.json")
+    }
+
+    // A missing or unreadable list just means only the default profile exists
+    pub fn load() -> Self {
+        let Ok(data) = fs::read_to_string(Self::path()) else {
+            return Profiles::default();
+        };
+
+        match serde_json::from_str::<Profiles>(&data) {
+            Ok(mut profiles) => {
+                if !profiles.names.iter().any(|name| name == DEFAULT_PROFILE) {
+                    profiles.names.insert(0, DEFAULT_PROFILE.to_string());
+                }
+                if !profiles.names.contains(&profiles.active) {
+                    profiles.active = DEFAULT_PROFILE.to_string();
+                }
+                profiles
+            }
+            Err(e) => {
+                eprintln!("Could not read profile list, using the default profile: {}", e);
+                Profiles::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(base_dir())?;
+        let json = serde_json::to_string_pretty(self)?;
+        write_private_atomic(&Self::path(), json.as_bytes())
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<(), String> {
+        validate_name(name)?;
+        if self.names.iter().any(|existing| existing == name) {
+            return Err(format!("A profile called {} already exists", name));
+        }
+
+        fs::create_dir_all(profile_dir(name)).map_err(|e| e.to_string())?;
+        self.names.push(name.to_string());
+        self.save().map_err(|e| e.to_string())
+    }
+
+    // Removes the profile and everything stored in its directory
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        if name == DEFAULT_PROFILE {
+            return Err("The default profile can't be deleted".to_string());
+        }
+        if name == self.active {
+            return Err("Switch to another profile before deleting this one".to_string());
+        }
+        if !self.names.iter().any(|existing| existing == name) {
+            return Err(format!("There is no profile called {}", name));
+        }
+
+        self.names.retain(|existing| existing != name);
+        self.save().map_err(|e| e.to_string())?;
+
+        match fs::remove_dir_all(profile_dir(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn set_active(&mut self, name: &str) -> Result<(), String> {
+        if !self.names.iter().any(|existing| existing == name) {
+            return Err(format!("There is no profile called {}", name));
+        }
+
+        self.active = name.to_string();
+        self.save().map_err(|e| e.to_string())
+    }
+}
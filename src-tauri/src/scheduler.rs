@@ -0,0 +1,224 @@
+use chrono::{DateTime, Utc};
+use std::time::{Duration, Instant};
+
+// Never sleep longer than this in one go, so a suspend is noticed soon after resume
+const MAX_TICK: Duration = Duration::from_secs(30);
+
+// How far the wall clock may drift from the monotonic clock between two ticks
+// before we treat it as a sleep/resume or a manual clock change
+const JUMP_THRESHOLD: Duration = Duration::from_secs(60);
+
+pub trait Clock {
+    // Wall-clock time, keeps running while the machine is suspended
+    fn wall(&self) -> DateTime<Utc>;
+    // Time since some fixed origin, does not advance while suspended
+    fn monotonic(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn wall(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Tick {
+    Wait(Duration),
+    Check,
+}
+
+pub struct Scheduler {
+    interval: Duration,
+    grace_period: Duration,
+    deadline: DateTime<Utc>,
+    last_wall: DateTime<Utc>,
+    last_monotonic: Duration,
+}
+
+impl Scheduler {
+    pub fn new(clock: &impl Clock, interval: Duration, grace_period: Duration) -> Scheduler {
+        let now = clock.wall();
+        Scheduler {
+            interval,
+            grace_period,
+            deadline: now + interval,
+            last_wall: now,
+            last_monotonic: clock.monotonic(),
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        if interval != self.interval {
+            // Keep the time already waited, just move the deadline
+            self.deadline = self.deadline - self.interval + interval;
+            self.interval = interval;
+        }
+    }
+
+    pub fn set_grace_period(&mut self, grace_period: Duration) {
+        self.grace_period = grace_period;
+    }
+
+    // Decide what the popup loop should do next.
+    // Deadlines are kept in wall-clock time, so time spent suspended counts towards
+    // the interval. When a jump is detected, every missed check collapses into a
+    // single catch-up check after the grace period.
+    pub fn next(&mut self, clock: &impl Clock) -> Tick {
+        let wall = clock.wall();
+        let monotonic = clock.monotonic();
+
+        if self.jumped(wall, monotonic) {
+            println!(
+                "Detected a clock jump (sleep/resume?). Catching up in {}s.",
+                self.grace_period.as_secs()
+            );
+            self.deadline = wall + self.grace_period;
+        }
+        self.last_wall = wall;
+        self.last_monotonic = monotonic;
+
+        if wall >= self.deadline {
+            self.deadline = wall + self.interval;
+            return Tick::Check;
+        }
+
+        let remaining = (self.deadline - wall).to_std().unwrap_or_default();
+        Tick::Wait(remaining.min(MAX_TICK))
+    }
+
+    fn jumped(&self, wall: DateTime<Utc>, monotonic: Duration) -> bool {
+        let monotonic_elapsed = monotonic.saturating_sub(self.last_monotonic);
+        let wall_elapsed = wall - self.last_wall;
+
+        match chrono::Duration::from_std(monotonic_elapsed) {
+            Ok(monotonic_elapsed) => {
+                let drift = (wall_elapsed - monotonic_elapsed).abs();
+                drift.to_std().unwrap_or_default() > JUMP_THRESHOLD
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // Wall and monotonic time move independently, like across a suspend or a clock change
+    struct FakeClock {
+        wall: Cell<DateTime<Utc>>,
+        monotonic: Cell<Duration>,
+    }
+
+    impl FakeClock {
+        fn new() -> FakeClock {
+            FakeClock {
+                wall: Cell::new(DateTime::from_timestamp(1_700_000_000, 0).unwrap()),
+                monotonic: Cell::new(Duration::ZERO),
+            }
+        }
+
+        // Time passing normally, both clocks together
+        fn advance(&self, by: Duration) {
+            self.advance_wall(by);
+            self.monotonic.set(self.monotonic.get() + by);
+        }
+
+        fn advance_wall(&self, by: Duration) {
+            self.wall.set(self.wall.get() + chrono::Duration::from_std(by).unwrap());
+        }
+
+        fn rewind_wall(&self, by: Duration) {
+            self.wall.set(self.wall.get() - chrono::Duration::from_std(by).unwrap());
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn wall(&self) -> DateTime<Utc> {
+            self.wall.get()
+        }
+
+        fn monotonic(&self) -> Duration {
+            self.monotonic.get()
+        }
+    }
+
+    const INTERVAL: Duration = Duration::from_secs(10 * 60);
+    const GRACE: Duration = Duration::from_secs(30);
+
+    // Sleep through whatever the scheduler asks for until it checks, counting the checks
+    fn run_for(scheduler: &mut Scheduler, clock: &FakeClock, total: Duration) -> usize {
+        let end = clock.monotonic() + total;
+        let mut checks = 0;
+        while clock.monotonic() < end {
+            match scheduler.next(clock) {
+                Tick::Wait(duration) => clock.advance(duration.min(end - clock.monotonic())),
+                Tick::Check => checks += 1,
+            }
+        }
+        checks
+    }
+
+    #[test]
+    fn fires_once_per_interval() {
+        let clock = FakeClock::new();
+        let mut scheduler = Scheduler::new(&clock, INTERVAL, GRACE);
+
+        assert_eq!(run_for(&mut scheduler, &clock, INTERVAL - Duration::from_secs(1)), 0);
+        assert_eq!(run_for(&mut scheduler, &clock, Duration::from_secs(1)), 0);
+        assert_eq!(scheduler.next(&clock), Tick::Check);
+        assert!(matches!(scheduler.next(&clock), Tick::Wait(_)));
+    }
+
+    #[test]
+    fn suspend_gives_one_check_after_grace_period() {
+        let clock = FakeClock::new();
+        let mut scheduler = Scheduler::new(&clock, INTERVAL, GRACE);
+        assert!(matches!(scheduler.next(&clock), Tick::Wait(_)));
+
+        // Asleep for several intervals: wall time moves, monotonic doesn't
+        clock.advance_wall(INTERVAL * 5);
+        assert!(matches!(scheduler.next(&clock), Tick::Wait(wait) if wait <= GRACE));
+
+        assert_eq!(run_for(&mut scheduler, &clock, GRACE), 0);
+        assert_eq!(scheduler.next(&clock), Tick::Check);
+        assert!(matches!(scheduler.next(&clock), Tick::Wait(_)));
+    }
+
+    #[test]
+    fn clock_set_back_reschedules() {
+        let clock = FakeClock::new();
+        let mut scheduler = Scheduler::new(&clock, INTERVAL, GRACE);
+        clock.advance(INTERVAL / 2);
+        assert!(matches!(scheduler.next(&clock), Tick::Wait(_)));
+
+        // Without the jump check this would wait another full interval and a half
+        clock.rewind_wall(INTERVAL);
+        assert!(matches!(scheduler.next(&clock), Tick::Wait(wait) if wait <= GRACE));
+        assert_eq!(run_for(&mut scheduler, &clock, GRACE), 0);
+        assert_eq!(scheduler.next(&clock), Tick::Check);
+    }
+}
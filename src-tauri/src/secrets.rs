@@ -0,0 +1,196 @@
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::{env, fs, io};
+
+use crate::config::write_private_atomic;
+use crate::profiles;
+
+const KEYRING_SERVICE: &str = "wanipop";
+
+// Where the WaniKani API token is kept
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    // The OS keyring, Secret Service on Linux
+    #[default]
+    Keyring,
+    // A file next to the config, encrypted with a local passphrase
+    EncryptedFile,
+    // In config.json in plain text, like older versions did
+    PlainFile,
+}
+
+pub trait SecretStore {
+    fn get(&self) -> io::Result<Option<String>>;
+    fn set(&self, secret: &str) -> io::Result<()>;
+    fn delete(&self) -> io::Result<()>;
+}
+
+// The plain file backend has no store of its own, the config writes the token itself
+pub fn store_for(backend: SecretBackend, account: &str) -> Option<Box<dyn SecretStore>> {
+    match backend {
+        SecretBackend::Keyring => Some(Box::new(KeyringStore::new(account))),
+        SecretBackend::EncryptedFile => Some(Box::new(EncryptedFileStore::new(account))),
+        SecretBackend::PlainFile => None,
+    }
+}
+
+// Show just enough of the token to tell two apart
+pub fn mask(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    let visible: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+    format!("{}{}", "•".repeat(8), visible)
+}
+
+fn other_error(e: impl ToString) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+pub struct KeyringStore {
+    account: String,
+}
+
+impl KeyringStore {
+    pub fn new(account: &str) -> KeyringStore {
+        KeyringStore {
+            account: account.to_string(),
+        }
+    }
+
+    fn entry(&self) -> io::Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, &self.account).map_err(other_error)
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn get(&self) -> io::Result<Option<String>> {
+        match self.entry()?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(other_error(e)),
+        }
+    }
+
+    fn set(&self, secret: &str) -> io::Result<()> {
+        self.entry()?.set_password(secret).map_err(other_error)
+    }
+
+    fn delete(&self) -> io::Result<()> {
+        match self.entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(other_error(e)),
+        }
+    }
+}
+
+// File layout: MAGIC | salt | nonce | ciphertext + tag
+const MAGIC: &[u8] = b"WPS1";
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+pub struct EncryptedFileStore {
+    path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(account: &str) -> EncryptedFileStore {
+        EncryptedFileStore {
+            path: secrets_dir().join(format!("{}.enc", account)),
+        }
+    }
+
+    // WANIPOP_SECRET_PASSPHRASE wins, otherwise a random one is made on first use
+    fn passphrase() -> io::Result<Vec<u8>> {
+        if let Ok(passphrase) = env::var("WANIPOP_SECRET_PASSPHRASE") {
+            return Ok(passphrase.into_bytes());
+        }
+
+        let path = secrets_dir().join("passphrase");
+        if path.exists() {
+            return fs::read(path);
+        }
+
+        let mut passphrase = [0u8; 32];
+        SystemRandom::new().fill(&mut passphrase).map_err(other_error)?;
+        fs::create_dir_all(secrets_dir())?;
+        write_private_atomic(&path, &passphrase)?;
+        Ok(passphrase.to_vec())
+    }
+
+    fn key(salt: &[u8]) -> io::Result<LessSafeKey> {
+        let mut key = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+            salt,
+            &Self::passphrase()?,
+            &mut key,
+        );
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key).map_err(other_error)?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn get(&self) -> io::Result<Option<String>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        let data = fs::read(&self.path)?;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Encrypted token file is damaged or the passphrase changed");
+
+        let rest = data.strip_prefix(MAGIC).ok_or_else(invalid)?;
+        if rest.len() < SALT_LEN + NONCE_LEN {
+            return Err(invalid());
+        }
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| invalid())?;
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = Self::key(salt)?
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| invalid())?;
+
+        String::from_utf8(plaintext.to_vec())
+            .map(Some)
+            .map_err(|_| invalid())
+    }
+
+    fn set(&self, secret: &str) -> io::Result<()> {
+        let rng = SystemRandom::new();
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut salt).map_err(other_error)?;
+        rng.fill(&mut nonce).map_err(other_error)?;
+
+        let mut in_out = secret.as_bytes().to_vec();
+        Self::key(&salt)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut in_out)
+            .map_err(other_error)?;
+
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&in_out);
+
+        fs::create_dir_all(secrets_dir())?;
+        write_private_atomic(&self.path, &data)
+    }
+
+    fn delete(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn secrets_dir() -> PathBuf {
+    profiles::base_dir().join("secrets")
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::write_private_atomic;
+use crate::profiles;
+use crate::wanikani::{self, SpacedRepetitionSystem, SpacedRepetitionSystemData, SubmittedReviewData};
+
+// WaniKani's regular system, used when we don't know which one an item is on
+const DEFAULT_SYSTEM_ID: u64 = 1;
+
+// The systems almost never change, so one fetch a day is plenty
+const CACHE_MAX_AGE_HOURS: i64 = 24;
+
+// Same for every account, so it sits next to the profiles rather than in one
+#[derive(Serialize, Deserialize)]
+struct CachedSystems {
+    fetched_at: DateTime<Utc>,
+    systems: Vec<SpacedRepetitionSystem>,
+}
+
+impl CachedSystems {
+    fn path() -> PathBuf {
+        profiles::base_dir().join("cache").join("spaced_repetition_systems.json")
+    }
+
+    fn load() -> Option<CachedSystems> {
+        let data = fs::read_to_string(Self::path()).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self)?;
+        write_private_atomic(&path, json.as_bytes())
+    }
+}
+
+pub struct SrsSystems {
+    systems: Vec<SpacedRepetitionSystem>,
+}
+
+impl SrsSystems {
+    pub fn new(systems: Vec<SpacedRepetitionSystem>) -> SrsSystems {
+        SrsSystems { systems }
+    }
+
+    // From the cache while it's fresh, otherwise from WaniKani. A stale cache still
+    // beats nothing when WaniKani can't be reached.
+    pub async fn load(client: &Client, api_key: String) -> Result<SrsSystems, String> {
+        let cached = CachedSystems::load();
+        if let Some(cached) = &cached {
+            if Utc::now() - cached.fetched_at < Duration::hours(CACHE_MAX_AGE_HOURS) {
+                return Ok(SrsSystems::new(cached.systems.clone()));
+            }
+        }
+
+        match wanikani::fetch_spaced_repetition_systems(client, api_key).await {
+            Ok(systems) => {
+                let fresh = CachedSystems {
+                    fetched_at: Utc::now(),
+                    systems,
+                };
+                if let Err(e) = fresh.save() {
+                    eprintln!("Could not cache SRS systems: {}", e);
+                }
+                Ok(SrsSystems::new(fresh.systems))
+            }
+            Err(e) => match cached {
+                Some(cached) => {
+                    eprintln!("Could not fetch SRS systems, using the cached ones: {}", e);
+                    Ok(SrsSystems::new(cached.systems))
+                }
+                None => Err(e.to_string()),
+            },
+        }
+    }
+
+    pub fn get(&self, id: Option<u64>) -> Option<&SpacedRepetitionSystemData> {
+        let id = id.unwrap_or(DEFAULT_SYSTEM_ID);
+        self.systems
+            .iter()
+            .find(|system| system.id == id)
+            .or_else(|| self.systems.iter().min_by_key(|system| system.id))
+            .map(|system| &system.data)
+    }
+}
+
+// The API only gives positions, the names follow from where they sit relative to
+// passing and burning. Enlightened and Master are the two stages before Burned.
+impl SpacedRepetitionSystemData {
+    pub fn stage_group(&self, position: u8) -> &'static str {
+        if position < self.starting_stage_position {
+            "Locked"
+        } else if position < self.passing_stage_position {
+            "Apprentice"
+        } else if position >= self.burning_stage_position {
+            "Burned"
+        } else if position + 1 == self.burning_stage_position {
+            "Enlightened"
+        } else if position + 2 == self.burning_stage_position {
+            "Master"
+        } else {
+            "Guru"
+        }
+    }
+
+    // e.g. "Apprentice 3", numbered within the group where the group has more than one stage
+    pub fn stage_name(&self, position: u8) -> String {
+        let group = self.stage_group(position);
+        match group {
+            "Apprentice" => format!("{} {}", group, position - self.starting_stage_position + 1),
+            "Guru" => format!("{} {}", group, position - self.passing_stage_position + 1),
+            _ => group.to_string(),
+        }
+    }
+
+    // How long an item waits at this stage, None for stages that never come up (locked, burned)
+    pub fn interval(&self, position: u8) -> Option<Duration> {
+        let stage = self.stages.iter().find(|stage| stage.position == position)?;
+        let interval = stage.interval? as i64;
+        match stage.interval_unit.as_deref()? {
+            "milliseconds" => Some(Duration::milliseconds(interval)),
+            "seconds" => Some(Duration::seconds(interval)),
+            "minutes" => Some(Duration::minutes(interval)),
+            "hours" => Some(Duration::hours(interval)),
+            "days" => Some(Duration::days(interval)),
+            "weeks" => Some(Duration::weeks(interval)),
+            _ => None,
+        }
+    }
+
+    // WaniKani rounds the next review down to the start of the hour
+    pub fn next_review_at(&self, reviewed_at: DateTime<Utc>, position: u8) -> Option<DateTime<Utc>> {
+        let at = reviewed_at + self.interval(position)?;
+        Some(at.duration_trunc(Duration::hours(1)).unwrap_or(at))
+    }
+}
+
+pub fn name_stages(review: &mut SubmittedReviewData, system: &SpacedRepetitionSystemData) {
+    review.starting_srs_stage_name = Some(system.stage_name(review.starting_srs_stage));
+    review.ending_srs_stage_name = Some(system.stage_name(review.ending_srs_stage));
+}
+
+pub fn schedule(review: &mut SubmittedReviewData, system: &SpacedRepetitionSystemData) {
+    review.next_review_at = system.next_review_at(review.created_at, review.ending_srs_stage);
+    review.passed = Some(review.ending_srs_stage >= system.passing_stage_position);
+}
+
+#[derive(Serialize, Default, Debug, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub moved_up: u32,
+    pub moved_down: u32,
+    pub reached_guru: u32,
+    pub burned: u32,
+}
+
+impl BatchSummary {
+    pub fn add(&mut self, review: &SubmittedReviewData, system: &SpacedRepetitionSystemData) {
+        let (start, end) = (review.starting_srs_stage, review.ending_srs_stage);
+        if end > start {
+            self.moved_up += 1;
+        } else if end < start {
+            self.moved_down += 1;
+        }
+        if start < system.passing_stage_position && end >= system.passing_stage_position {
+            self.reached_guru += 1;
+        }
+        if start < system.burning_stage_position && end >= system.burning_stage_position {
+            self.burned += 1;
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let mut message = format!("Batch done! {} moved up, {} moved down.", self.moved_up, self.moved_down);
+        if self.reached_guru > 0 {
+            message.push_str(&format!("\n{} reached Guru! 🎉", self.reached_guru));
+        }
+        if self.burned > 0 {
+            message.push_str(&format!("\n{} burned! 🔥", self.burned));
+        }
+        message
+    }
+}
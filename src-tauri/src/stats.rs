@@ -0,0 +1,343 @@
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::history::{History, HistoryEntry, HistoryQuery, QuestionType};
+use crate::wanikani::SpacedRepetitionSystemData;
+
+// Shown in this order, even when there were no reviews of that type
+const SUBJECT_TYPES: [&str; 4] = ["radical", "kanji", "vocabulary", "kana_vocabulary"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsRange {
+    Week,
+    Month,
+    Quarter,
+    Year,
+    All,
+}
+
+impl StatsRange {
+    fn days(self) -> Option<u64> {
+        match self {
+            StatsRange::Week => Some(7),
+            StatsRange::Month => Some(30),
+            StatsRange::Quarter => Some(90),
+            StatsRange::Year => Some(365),
+            StatsRange::All => None,
+        }
+    }
+}
+
+// Ready to hand to a chart: one label per point, in order
+#[derive(Serialize, Default, Debug)]
+pub struct Series {
+    pub labels: Vec<String>,
+    pub values: Vec<u32>,
+}
+
+#[derive(Serialize, Default, Clone, Copy, Debug)]
+pub struct Accuracy {
+    pub correct: u32,
+    pub total: u32,
+    // None when there was nothing to answer, so charts leave a gap instead of showing 0%
+    pub percent: Option<f64>,
+}
+
+impl Accuracy {
+    fn add(&mut self, correct: bool) {
+        self.total += 1;
+        if correct {
+            self.correct += 1;
+        }
+        self.percent = Some(self.correct as f64 * 100.0 / self.total as f64);
+    }
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Breakdown {
+    pub labels: Vec<String>,
+    pub accuracy: Vec<Option<f64>>,
+    pub reviews: Vec<u32>,
+}
+
+impl Breakdown {
+    fn push(&mut self, label: String, accuracy: Accuracy) {
+        self.labels.push(label);
+        self.accuracy.push(accuracy.percent);
+        self.reviews.push(accuracy.total);
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Stats {
+    pub range: StatsRange,
+    // Local dates, both ends included
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    pub total_reviews: u32,
+    // One point per day, and per week starting on Monday
+    pub daily: Series,
+    pub weekly: Series,
+    pub overall_accuracy: Accuracy,
+    pub meaning_accuracy: Accuracy,
+    pub reading_accuracy: Accuracy,
+    pub by_subject_type: Breakdown,
+    // By the stage the item was at when it was reviewed
+    pub by_srs_stage: Breakdown,
+    // Days in a row with at least one review, over the whole history
+    pub current_streak: u32,
+    pub longest_streak: u32,
+}
+
+pub fn local_day(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&Local).date_naive()
+}
+
+fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Days::new(day.weekday().num_days_from_monday() as u64)
+}
+
+// Named after the user's SRS system, just numbered when it couldn't be loaded
+fn srs_stage_name(stage: u8, system: Option<&SpacedRepetitionSystemData>) -> String {
+    match system {
+        Some(system) => system.stage_name(stage),
+        None => format!("Stage {}", stage),
+    }
+}
+
+// Whether the review asked for a reading. Kana-only vocabulary and radicals don't have one.
+pub fn has_reading(entry: &HistoryEntry) -> bool {
+    if !entry.attempts.is_empty() {
+        return entry.attempts.iter().any(|a| a.question_type == QuestionType::Reading);
+    }
+    match entry.subject_type.as_deref() {
+        Some("kanji") | Some("vocabulary") => true,
+        Some(_) => false,
+        None => entry.incorrect_reading_answers > 0,
+    }
+}
+
+fn streaks(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> (u32, u32) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut last: Option<NaiveDate> = None;
+
+    for &day in days.range(..=today) {
+        run = if last.and_then(|last| last.succ_opt()) == Some(day) {
+            run + 1
+        } else {
+            1
+        };
+        longest = longest.max(run);
+        last = Some(day);
+    }
+
+    // Today isn't over yet, so a streak that reached yesterday is still going
+    let current = match last {
+        Some(day) if day == today || day.succ_opt() == Some(today) => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+// Work out the stats for the reviews in range. review_days is every day that has
+// reviews in the whole history, for the streaks. The history doesn't keep which SRS
+// system a review was on, so the stages are named after the regular one.
+pub fn compute(
+    entries: &[HistoryEntry],
+    review_days: &BTreeSet<NaiveDate>,
+    range: StatsRange,
+    today: NaiveDate,
+    system: Option<&SpacedRepetitionSystemData>,
+) -> Stats {
+    let since = match range.days() {
+        Some(days) => today - Days::new(days - 1),
+        None => review_days.first().copied().unwrap_or(today).min(today),
+    };
+
+    let mut per_day: HashMap<NaiveDate, u32> = HashMap::new();
+    let mut overall = Accuracy::default();
+    let mut meaning = Accuracy::default();
+    let mut reading = Accuracy::default();
+    let mut by_type: BTreeMap<String, Accuracy> = BTreeMap::new();
+    let mut by_stage: BTreeMap<u8, Accuracy> = BTreeMap::new();
+    let mut total_reviews = 0;
+
+    for entry in entries {
+        let day = local_day(entry.created_at);
+        if day < since || day > today {
+            continue;
+        }
+        total_reviews += 1;
+        *per_day.entry(day).or_default() += 1;
+
+        let meaning_correct = entry.incorrect_meaning_answers == 0;
+        let reading_correct = entry.incorrect_reading_answers == 0;
+        meaning.add(meaning_correct);
+        if has_reading(entry) {
+            reading.add(reading_correct);
+        }
+
+        // A stray reading miss on a review that never asked for one doesn't count against it
+        let correct = meaning_correct && (!has_reading(entry) || reading_correct);
+        overall.add(correct);
+        let subject_type = entry.subject_type.clone().unwrap_or_else(|| "unknown".to_string());
+        by_type.entry(subject_type).or_default().add(correct);
+        by_stage.entry(entry.starting_srs_stage).or_default().add(correct);
+    }
+
+    let mut daily = Series::default();
+    for day in since.iter_days().take_while(|day| *day <= today) {
+        daily.labels.push(day.to_string());
+        daily.values.push(per_day.get(&day).copied().unwrap_or(0));
+    }
+
+    let mut weekly = Series::default();
+    let mut week = week_start(since);
+    while week <= today {
+        let end = week + Days::new(7);
+        weekly.labels.push(week.to_string());
+        weekly.values.push(
+            per_day
+                .iter()
+                .filter(|(day, _)| **day >= week && **day < end)
+                .map(|(_, count)| count)
+                .sum(),
+        );
+        week = end;
+    }
+
+    let mut by_subject_type = Breakdown::default();
+    for subject_type in SUBJECT_TYPES {
+        let accuracy = by_type.remove(subject_type).unwrap_or_default();
+        by_subject_type.push(subject_type.to_string(), accuracy);
+    }
+    for (subject_type, accuracy) in by_type {
+        by_subject_type.push(subject_type, accuracy);
+    }
+
+    let mut by_srs_stage = Breakdown::default();
+    // Every stage an item can be reviewed at, even without reviews
+    let reviewed_stages = system.map_or(0..0, |system| system.starting_stage_position..system.burning_stage_position);
+    for stage in reviewed_stages {
+        let accuracy = by_stage.remove(&stage).unwrap_or_default();
+        by_srs_stage.push(srs_stage_name(stage, system), accuracy);
+    }
+    for (stage, accuracy) in by_stage {
+        by_srs_stage.push(srs_stage_name(stage, system), accuracy);
+    }
+
+    let (current_streak, longest_streak) = streaks(review_days, today);
+
+    Stats {
+        range,
+        since,
+        until: today,
+        total_reviews,
+        daily,
+        weekly,
+        overall_accuracy: overall,
+        meaning_accuracy: meaning,
+        reading_accuracy: reading,
+        by_subject_type,
+        by_srs_stage,
+        current_streak,
+        longest_streak,
+    }
+}
+
+pub fn load(
+    history: &History,
+    range: StatsRange,
+    system: Option<&SpacedRepetitionSystemData>,
+) -> rusqlite::Result<Stats> {
+    let today = Local::now().date_naive();
+    let review_days: BTreeSet<NaiveDate> = history
+        .review_times()?
+        .into_iter()
+        .map(local_day)
+        .collect();
+
+    // A day early is enough to cover any timezone, compute() trims to local days
+    let since = range
+        .days()
+        .and_then(|days| today.checked_sub_days(Days::new(days)))
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc());
+    let entries = history.query(&HistoryQuery {
+        since,
+        practice: Some(false),
+        ..Default::default()
+    })?;
+
+    Ok(compute(&entries, &review_days, range, today, system))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap()
+    }
+
+    fn days(ds: &[u32]) -> BTreeSet<NaiveDate> {
+        ds.iter().map(|d| day(*d)).collect()
+    }
+
+    // Reviewed at noon local time on the given day
+    fn entry(d: u32, subject_type: &str, incorrect_meaning: u8, incorrect_reading: u8) -> HistoryEntry {
+        let noon = Local.from_local_datetime(&day(d).and_hms_opt(12, 0, 0).unwrap()).unwrap();
+        HistoryEntry {
+            assignment_id: 1,
+            subject_id: 1,
+            subject_type: Some(subject_type.to_string()),
+            characters: None,
+            starting_srs_stage: 1,
+            ending_srs_stage: 2,
+            incorrect_meaning_answers: incorrect_meaning,
+            incorrect_reading_answers: incorrect_reading,
+            time_taken_ms: 0,
+            created_at: noon.with_timezone(&Utc),
+            attempts: Vec::new(),
+            practice: false,
+        }
+    }
+
+    #[test]
+    fn streaks_across_gaps() {
+        assert_eq!(streaks(&days(&[]), day(10)), (0, 0));
+        assert_eq!(streaks(&days(&[10]), day(10)), (1, 1));
+        // A gap day ends a streak
+        assert_eq!(streaks(&days(&[1, 2, 3, 5, 6]), day(6)), (2, 3));
+        assert_eq!(streaks(&days(&[1, 2, 3, 5, 6]), day(8)), (0, 3));
+    }
+
+    #[test]
+    fn streak_goes_on_until_today_is_over() {
+        assert_eq!(streaks(&days(&[7, 8, 9]), day(10)), (3, 3));
+        assert_eq!(streaks(&days(&[7, 8, 9]), day(11)), (0, 3));
+        // Days after today aren't counted yet
+        assert_eq!(streaks(&days(&[9, 10, 11]), day(10)), (2, 2));
+    }
+
+    #[test]
+    fn reading_misses_only_count_with_a_reading_question() {
+        let entries = [
+            entry(10, "kanji", 0, 0),
+            entry(10, "kanji", 0, 1),
+            entry(10, "radical", 0, 0),
+            // No reading question, so the reading count means nothing
+            entry(10, "radical", 0, 1),
+            entry(10, "kana_vocabulary", 1, 0),
+        ];
+        let stats = compute(&entries, &days(&[10]), StatsRange::Week, day(10), None);
+
+        assert_eq!((stats.overall_accuracy.correct, stats.overall_accuracy.total), (3, 5));
+        assert_eq!((stats.meaning_accuracy.correct, stats.meaning_accuracy.total), (4, 5));
+        assert_eq!((stats.reading_accuracy.correct, stats.reading_accuracy.total), (1, 2));
+    }
+}
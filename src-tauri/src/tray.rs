@@ -0,0 +1,275 @@
+use crate::levels;
+use crate::profiles::Profiles;
+use crate::wanikani::{self, PauseReason, SummaryData, TimeBucket};
+use crate::AppState;
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::image::Image;
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::{AppHandle, Manager, Wry};
+use tokio::time::sleep;
+
+pub const TRAY_ID: &str = "main";
+// Menu ids for switching profile are this followed by the profile name
+pub const PROFILE_MENU_PREFIX: &str = "profile:";
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// 3x5 pixel glyphs for the badge, one row per byte, lowest three bits used
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const DIGITS: [[u8; GLYPH_HEIGHT]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b111, 0b001, 0b111, 0b100, 0b111], // 2
+    [0b111, 0b001, 0b111, 0b001, 0b111], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b111, 0b001, 0b111], // 5
+    [0b111, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b010, 0b010, 0b010], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b111], // 9
+];
+const PLUS: [u8; GLYPH_HEIGHT] = [0b000, 0b010, 0b111, 0b010, 0b000];
+
+const BADGE_COLOR: [u8; 4] = [0xe0, 0x1e, 0x5a, 0xff];
+const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+// Badged versions of the tray icon, rendered once per review count
+pub struct BadgeCache {
+    base: Image<'static>,
+    icons: HashMap<usize, Image<'static>>,
+}
+
+impl BadgeCache {
+    pub fn new(base: Image<'static>) -> BadgeCache {
+        BadgeCache {
+            base,
+            icons: HashMap::new(),
+        }
+    }
+
+    pub fn icon_for(&mut self, count: usize) -> Image<'static> {
+        if count == 0 {
+            return self.base.clone();
+        }
+
+        // Everything past 99 draws the same "99+", so it shares one entry
+        let count = count.min(100);
+        let base = &self.base;
+        self.icons
+            .entry(count)
+            .or_insert_with(|| {
+                let (width, height) = (base.width(), base.height());
+                let rgba = draw_badge(base.rgba(), width as usize, height as usize, count);
+                Image::new_owned(rgba, width, height)
+            })
+            .clone()
+    }
+}
+
+pub fn available_count(buckets: &[TimeBucket], now: DateTime<Utc>) -> usize {
+    buckets
+        .iter()
+        .filter(|b| b.available_at <= now)
+        .map(|b| b.subject_ids.len())
+        .sum()
+}
+
+pub fn tooltip_text(summary: &SummaryData, now: DateTime<Utc>) -> String {
+    let reviews = available_count(&summary.reviews, now);
+    let lessons = available_count(&summary.lessons, now);
+
+    let mut text = format!("WaniPOP! {} reviews, {} lessons", reviews, lessons);
+    let upcoming = summary
+        .reviews
+        .iter()
+        .find(|b| b.available_at > now && !b.subject_ids.is_empty());
+    if let Some(bucket) = upcoming {
+        text.push_str(&format!(
+            ", {} more at {}",
+            bucket.subject_ids.len(),
+            bucket.available_at.with_timezone(&Local).format("%H:%M")
+        ));
+    } else if let Some(next) = summary.next_reviews_at.filter(|next| *next > now) {
+        text.push_str(&format!(
+            ", next at {}",
+            next.with_timezone(&Local).format("%H:%M")
+        ));
+    }
+    text
+}
+
+// Draw a filled circle with the count in the bottom right corner of the icon
+pub fn draw_badge(base: &[u8], width: usize, height: usize, count: usize) -> Vec<u8> {
+    let mut rgba = base.to_vec();
+    let size = width.min(height);
+
+    let glyphs: Vec<&[u8; GLYPH_HEIGHT]> = if count > 99 {
+        vec![&DIGITS[9], &DIGITS[9], &PLUS]
+    } else {
+        count
+            .to_string()
+            .bytes()
+            .map(|b| &DIGITS[(b - b'0') as usize])
+            .collect()
+    };
+
+    // The badge covers a bit more than half the icon, text gets a one pixel gap between glyphs
+    let diameter = size * 6 / 10;
+    let text_columns = glyphs.len() * (GLYPH_WIDTH + 1) - 1;
+    let scale = ((diameter * 7 / 10) / text_columns).max(1);
+
+    let radius = diameter as f32 / 2.0;
+    let center_x = (width - diameter) as f32 + radius;
+    let center_y = (height - diameter) as f32 + radius;
+
+    for y in (height - diameter)..height {
+        for x in (width - diameter)..width {
+            let dx = x as f32 + 0.5 - center_x;
+            let dy = y as f32 + 0.5 - center_y;
+            if dx * dx + dy * dy <= radius * radius {
+                put_pixel(&mut rgba, width, x, y, BADGE_COLOR);
+            }
+        }
+    }
+
+    let text_width = text_columns * scale;
+    let text_height = GLYPH_HEIGHT * scale;
+    let left = (center_x as usize).saturating_sub(text_width / 2);
+    let top = (center_y as usize).saturating_sub(text_height / 2);
+
+    for (i, glyph) in glyphs.iter().enumerate() {
+        let glyph_left = left + i * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        let x = glyph_left + col * scale + sx;
+                        let y = top + row * scale + sy;
+                        if x < width && y < height {
+                            put_pixel(&mut rgba, width, x, y, TEXT_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    rgba
+}
+
+fn put_pixel(rgba: &mut [u8], width: usize, x: usize, y: usize, color: [u8; 4]) {
+    let offset = (y * width + x) * 4;
+    rgba[offset..offset + 4].copy_from_slice(&color);
+}
+
+pub fn build_menu(app_handle: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let profiles = Profiles::load();
+
+    let review_now = MenuItem::with_id(app_handle, "review_now", "Start Reviews", true, None::<&str>)?;
+    let practice_menu = Submenu::with_items(
+        app_handle,
+        "Practice",
+        true,
+        &[
+            &MenuItem::with_id(app_handle, "practice_mistakes", "Recent Mistakes", true, None::<&str>)?,
+            &MenuItem::with_id(app_handle, "practice_burned", "Burned Items", true, None::<&str>)?,
+        ],
+    )?;
+    let profile_menu = Submenu::new(app_handle, "Profile", true)?;
+    for name in &profiles.names {
+        let item = CheckMenuItem::with_id(
+            app_handle,
+            format!("{}{}", PROFILE_MENU_PREFIX, name),
+            name,
+            true,
+            *name == profiles.active,
+            None::<&str>,
+        )?;
+        profile_menu.append(&item)?;
+    }
+    let separator = PredefinedMenuItem::separator(app_handle)?;
+    let quit = MenuItem::with_id(app_handle, "quit", "Quit", true, None::<&str>)?;
+
+    Menu::with_items(
+        app_handle,
+        &[&review_now, &practice_menu, &profile_menu, &separator, &quit],
+    )
+}
+
+// Call after the profile list or the active profile changes
+pub fn rebuild_menu(app_handle: &AppHandle) {
+    let Some(tray) = app_handle.tray_by_id(TRAY_ID) else {
+        return;
+    };
+
+    match build_menu(app_handle) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => eprintln!("Could not rebuild tray menu: {}", e),
+    }
+}
+
+// Nothing will pop up, say why instead of counting reviews
+pub fn show_paused(app_handle: &AppHandle, reason: &PauseReason, badges: &mut BadgeCache) {
+    let Some(tray) = app_handle.tray_by_id(TRAY_ID) else {
+        return;
+    };
+
+    let _ = tray.set_tooltip(Some(format!("WaniPOP! {}", reason.message())));
+    let _ = tray.set_icon(Some(badges.icon_for(0)));
+}
+
+pub fn update_tray(app_handle: &AppHandle, summary: &SummaryData, badges: &mut BadgeCache) {
+    let Some(tray) = app_handle.tray_by_id(TRAY_ID) else {
+        return;
+    };
+
+    let now = Utc::now();
+    let _ = tray.set_tooltip(Some(tooltip_text(summary, now)));
+    let _ = tray.set_icon(Some(badges.icon_for(available_count(&summary.reviews, now))));
+}
+
+// Keep the tray tooltip and badge in sync with the WaniKani summary
+pub async fn refresh_loop(app_handle: AppHandle, base_icon: Image<'static>) {
+    let mut badges = BadgeCache::new(base_icon);
+
+    loop {
+        let (client, api_key) = {
+            let state = app_handle.state::<AppState>();
+            let cfg = state.config.lock().unwrap();
+            (state.http_client.clone(), cfg.wanikani_api_key.clone())
+        };
+
+        if let Some(api_key) = api_key {
+            let paused = match wanikani::fetch_user(&client, api_key.clone()).await {
+                Ok(user) => {
+                    let account = app_handle.state::<AppState>().remember_account(&api_key, &user);
+                    levels::check_level(&app_handle, &account);
+                    account.pause_reason
+                }
+                Err(e) => {
+                    eprintln!("Error checking vacation and subscription: {}", e);
+                    None
+                }
+            };
+
+            if let Some(reason) = paused {
+                show_paused(&app_handle, &reason, &mut badges);
+            } else {
+                match wanikani::fetch_summary(&client, api_key).await {
+                    Ok(summary) => update_tray(&app_handle, &summary, &mut badges),
+                    Err(e) => eprintln!("Error refreshing tray summary: {}", e),
+                }
+            }
+        }
+
+        sleep(REFRESH_INTERVAL).await;
+    }
+}
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use reqwest::{Client, RequestBuilder};
+use reqwest::{Client, RequestBuilder, StatusCode};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize)]
@@ -110,6 +110,19 @@ pub struct SubmittedReviewData {
     pub ending_srs_stage: u8,
     pub incorrect_meaning_answers: u8,
     pub incorrect_reading_answers: u8,
+    // Not part of WaniKani's response, filled in from the SRS system, see srs.rs
+    #[serde(default)]
+    pub starting_srs_stage_name: Option<String>,
+    #[serde(default)]
+    pub ending_srs_stage_name: Option<String>,
+    // Also worked out locally: when the item comes back up, None once burned, and whether
+    // it's at or past the passing stage (Guru)
+    #[serde(default)]
+    pub next_review_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub passed: Option<bool>,
+    #[serde(default)]
+    pub spaced_repetition_system_id: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -118,6 +131,76 @@ pub struct FailedSubmittedReviewData {
     pub error: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ReviewStatistic {
+    pub id: u64,
+    pub data_updated_at: DateTime<Utc>,
+    pub data: ReviewStatisticData,
+}
+
+// Lifetime answer counts for one subject. Streaks start at 1 and count correct answers in a row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewStatisticData {
+    pub subject_id: u64,
+    pub subject_type: String,
+    pub meaning_correct: u32,
+    pub meaning_incorrect: u32,
+    pub meaning_max_streak: u32,
+    pub meaning_current_streak: u32,
+    pub reading_correct: u32,
+    pub reading_incorrect: u32,
+    pub reading_max_streak: u32,
+    pub reading_current_streak: u32,
+    pub percentage_correct: u8,
+    pub hidden: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LevelProgression {
+    pub id: u64,
+    pub data_updated_at: DateTime<Utc>,
+    pub data: LevelProgressionData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelProgressionData {
+    pub level: u32,
+    pub created_at: DateTime<Utc>,
+    pub unlocked_at: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub passed_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    // Set when a reset takes the user back below this level
+    pub abandoned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpacedRepetitionSystem {
+    pub id: u64,
+    pub data_updated_at: DateTime<Utc>,
+    pub data: SpacedRepetitionSystemData,
+}
+
+// Stage positions mark where an item unlocks, starts after lessons, passes (Guru) and burns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpacedRepetitionSystemData {
+    pub name: String,
+    pub description: String,
+    pub unlocking_stage_position: u8,
+    pub starting_stage_position: u8,
+    pub passing_stage_position: u8,
+    pub burning_stage_position: u8,
+    pub stages: Vec<SrsStage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SrsStage {
+    pub interval: Option<u64>,
+    // milliseconds, seconds, minutes, hours, days or weeks
+    pub interval_unit: Option<String>,
+    pub position: u8,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Subscription {
     pub active: bool,
@@ -157,6 +240,75 @@ pub struct UserData {
     pub level: u32,
     pub profile_url: String,
     pub subscription: Subscription,
+    // Set while vacation mode is on, reviews don't come due until it's turned off
+    pub current_vacation_started_at: Option<DateTime<Utc>>,
+}
+
+// Why there's no point nagging the user right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PauseReason {
+    Vacation { since: DateTime<Utc> },
+    // No active subscription and already past the free levels, so most reviews are locked
+    SubscriptionLapsed { max_level_granted: u8 },
+}
+
+impl PauseReason {
+    pub fn message(&self) -> String {
+        match self {
+            PauseReason::Vacation { since } => format!(
+                "Paused, on vacation since {}",
+                since.with_timezone(&chrono::Local).format("%Y-%m-%d")
+            ),
+            PauseReason::SubscriptionLapsed { max_level_granted } => format!(
+                "Paused, your subscription has lapsed (levels 1-{} only)",
+                max_level_granted
+            ),
+        }
+    }
+}
+
+impl UserData {
+    pub fn pause_reason(&self) -> Option<PauseReason> {
+        if let Some(since) = self.current_vacation_started_at {
+            return Some(PauseReason::Vacation { since });
+        }
+        if !self.subscription.active && self.level > self.subscription.max_level_granted as u32 {
+            return Some(PauseReason::SubscriptionLapsed {
+                max_level_granted: self.subscription.max_level_granted,
+            });
+        }
+        None
+    }
+}
+
+// The parts of the user that decide what can be fetched and whether to pop up. Kept
+// with the key they were fetched with, so another account never gets them.
+#[derive(Debug, Clone)]
+pub struct AccountStatus {
+    pub api_key: String,
+    pub level: u32,
+    pub max_level_granted: u8,
+    pub pause_reason: Option<PauseReason>,
+}
+
+impl AccountStatus {
+    pub fn new(api_key: &str, user: &UserData) -> AccountStatus {
+        AccountStatus {
+            api_key: api_key.to_string(),
+            level: user.level,
+            max_level_granted: user.subscription.max_level_granted,
+            pause_reason: user.pause_reason(),
+        }
+    }
+}
+
+// The levels filter for everything the subscription gives access to, e.g. "levels=1,2,3"
+pub fn granted_levels(max_level_granted: u8) -> String {
+    let levels = (1..=max_level_granted)
+        .map(|level| level.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("levels={}", levels)
 }
 
 #[derive(Debug, Deserialize)]
@@ -189,6 +341,35 @@ pub async fn fetch_user(client: &Client, api_key: String) -> Result<UserData, re
     Ok(user_data)
 }
 
+// WaniKani has no endpoint listing a token's permissions, so send a review that can never
+// be valid. Tokens allowed to create reviews get a validation error (422), the rest get 403.
+// Anything else, like rate limiting or an outage, says nothing about the token.
+pub async fn can_create_reviews(client: &Client, api_key: String) -> Result<bool, String> {
+    let payload = ReviewPayload {
+        review: ReviewResult {
+            assignment_id: 0,
+            incorrect_meaning_answers: 0,
+            incorrect_reading_answers: 0,
+            created_at: None,
+        },
+    };
+
+    let res = client
+        .post("https://api.wanikani.com/v2/reviews")
+        .wanikani_headers(api_key)
+        .header("Content-Type", "application/json")
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match res.status() {
+        StatusCode::UNPROCESSABLE_ENTITY => Ok(true),
+        StatusCode::FORBIDDEN => Ok(false),
+        status => Err(format!("unexpected response from WaniKani ({})", status)),
+    }
+}
+
 pub async fn fetch_summary(
     client: &Client,
     api_key: String,
@@ -213,10 +394,12 @@ pub fn has_available_reviews(summary: &SummaryData) -> bool {
         .any(|bucket| bucket.available_at <= now && !bucket.subject_ids.is_empty())
 }
 
+// Only subjects within max_level_granted, WaniKani refuses the rest anyway
 pub async fn fetch_assignments_for_subjects(
     client: &Client,
     api_key: String,
     subject_ids: &[u64],
+    max_level_granted: u8,
 ) -> Result<Vec<Assignment>, reqwest::Error> {
     let ids = subject_ids
         .iter()
@@ -225,8 +408,9 @@ pub async fn fetch_assignments_for_subjects(
         .join(",");
 
     let url = format!(
-        "https://api.wanikani.com/v2/assignments?subject_ids={}",
-        ids
+        "https://api.wanikani.com/v2/assignments?subject_ids={}&{}",
+        ids,
+        granted_levels(max_level_granted)
     );
 
     let res = client
@@ -245,6 +429,7 @@ pub async fn fetch_subjects(
     client: &Client,
     api_key: String,
     subject_ids: &[u64],
+    max_level_granted: u8,
 ) -> Result<Vec<Subject>, String> {
     let ids = subject_ids
         .iter()
@@ -252,7 +437,11 @@ pub async fn fetch_subjects(
         .collect::<Vec<_>>()
         .join(",");
 
-    let url = format!("https://api.wanikani.com/v2/subjects?ids={}", ids);
+    let url = format!(
+        "https://api.wanikani.com/v2/subjects?ids={}&{}",
+        ids,
+        granted_levels(max_level_granted)
+    );
 
     let resp = client
         .get(&url)
@@ -293,3 +482,73 @@ pub async fn submit_review(
 
     Ok(res)
 }
+
+// Follow next_url until the whole collection has been fetched
+async fn fetch_all_pages<T: serde::de::DeserializeOwned>(
+    client: &Client,
+    api_key: String,
+    url: String,
+) -> Result<Vec<T>, reqwest::Error> {
+    let mut items = Vec::new();
+    let mut next_url = Some(url);
+
+    while let Some(url) = next_url {
+        let page = client
+            .get(&url)
+            .wanikani_headers(api_key.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<CollectionResponse<T>>()
+            .await?;
+
+        items.extend(page.data);
+        next_url = page.pages.next_url;
+    }
+
+    Ok(items)
+}
+
+// Every assignment matching the filters, e.g. "burned=true" or "levels=5&started=true"
+pub async fn fetch_assignments(
+    client: &Client,
+    api_key: String,
+    filters: &str,
+) -> Result<Vec<Assignment>, reqwest::Error> {
+    let url = format!("https://api.wanikani.com/v2/assignments?{}", filters);
+    fetch_all_pages(client, api_key, url).await
+}
+
+pub async fn fetch_level_progressions(
+    client: &Client,
+    api_key: String,
+) -> Result<Vec<LevelProgression>, reqwest::Error> {
+    let url = "https://api.wanikani.com/v2/level_progressions".to_string();
+    fetch_all_pages(client, api_key, url).await
+}
+
+pub async fn fetch_spaced_repetition_systems(
+    client: &Client,
+    api_key: String,
+) -> Result<Vec<SpacedRepetitionSystem>, reqwest::Error> {
+    let url = "https://api.wanikani.com/v2/spaced_repetition_systems".to_string();
+    fetch_all_pages(client, api_key, url).await
+}
+
+// Review statistics for every subject the user has reviewed, or only those changed since
+// updated_after. Hidden ones are included so a cached copy hears about them too.
+pub async fn fetch_review_statistics(
+    client: &Client,
+    api_key: String,
+    updated_after: Option<DateTime<Utc>>,
+) -> Result<Vec<ReviewStatistic>, reqwest::Error> {
+    let mut url = "https://api.wanikani.com/v2/review_statistics".to_string();
+    if let Some(updated_after) = updated_after {
+        url.push_str(&format!(
+            "?updated_after={}",
+            updated_after.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        ));
+    }
+
+    fetch_all_pages(client, api_key, url).await
+}
@@ -1,6 +1,6 @@
 import { defineStore } from 'pinia'
 import { ref, computed } from 'vue'
-import { ReviewCard, ReviewResponse, ReviewResult, ReviewTask, WaniKaniResult } from '../types'
+import { PracticeResult, ReviewCard, ReviewDetails, ReviewResponse, ReviewResult, ReviewTask, WaniKaniResult } from '../types'
 
 export const useStudyStore = defineStore('study', () => {
   let storeReviewItems  = ref({} as Record<number, ReviewCard>)
@@ -34,13 +34,23 @@ export const useStudyStore = defineStore('study', () => {
     storeReviewStack.value.unshift(inItem)
   }
 
-  function resultRecordMark(reviewItem: ReviewTask, correct: boolean, attempt: string) {
+  function resultRecordMark(reviewItem: ReviewTask, correct: boolean, attempt: string, timeTakenMs: number) {
     const subjectId = reviewItem.review_item.subject_id
     if(!storeResultRecord.value[subjectId]) {
       storeResultRecord.value[subjectId] = {} as ReviewResult
       storeResultRecord.value[subjectId].subjectData = reviewItem.review_item
+      storeResultRecord.value[subjectId].attempts = []
     }
 
+    //Every answer goes into the local history, not just the first one
+    storeResultRecord.value[subjectId].attempts.push({
+      question_type: reviewItem.review_type,
+      answer: attempt,
+      correct,
+      time_taken_ms: Math.round(timeTakenMs),
+      answered_at: new Date().toISOString(),
+    })
+
     storeResultRecord.value[subjectId].assignment_id = reviewItem.review_item.assignment_id
     storeResultRecord.value[subjectId].subject_id = reviewItem.review_item.subject_id
     storeResultRecord.value[subjectId].characters = reviewItem.review_item.characters
@@ -89,6 +99,12 @@ export const useStudyStore = defineStore('study', () => {
       throw new Error(`Trying to flip ID of storeResultRecord.value that doesn't exist. subjectId: ${subjectId}`)
     }
 
+    //The answer being flipped is the last one given for this question
+    const lastAttempt = storeResultRecord.value[subjectId].attempts
+      .filter(a => a.question_type == meaningOrReading)
+      .pop()
+    if(lastAttempt) lastAttempt.correct = !lastAttempt.correct
+
     if(meaningOrReading == 'meaning' && storeResultRecord.value[subjectId].meaning) {
       if(storeResultRecord.value[subjectId].meaning == 'correct')
         storeResultRecord.value[subjectId].meaning = 'incorrect'
@@ -130,6 +146,12 @@ export const useStudyStore = defineStore('study', () => {
       storeResultRecord.value: `, storeResultRecord.value)
   }
 
+  //Radicals and kana-only vocabulary never get a reading question, so there's nothing to count.
+  //Only for practice, what's sent to WaniKani stays as it was.
+  function incorrectReadingAnswers(result: ReviewResult): number {
+    return result.reading == 'incorrect' ? 1 : 0
+  }
+
   function getSubmittableResults(): Array<WaniKaniResult> {
     return Object.values(resultRecord.value).map(result => ({
       assignment_id: result.assignment_id,
@@ -148,6 +170,31 @@ export const useStudyStore = defineStore('study', () => {
     }))
   }
 
+  function getReviewDetails(results: Array<WaniKaniResult>): Array<ReviewDetails> {
+    const assignmentIds = results.map(r => r.assignment_id)
+    return Object.values(resultRecord.value)
+      .filter(result => assignmentIds.includes(result.assignment_id))
+      .map(result => ({
+        assignment_id: result.assignment_id,
+        subject_type: result.subjectData.subject_type,
+        characters: result.characters,
+        attempts: result.attempts,
+      }))
+  }
+
+  function getPracticeResults(): Array<PracticeResult> {
+    return Object.values(resultRecord.value).map(result => ({
+      assignment_id: result.assignment_id,
+      subject_id: result.subject_id,
+      subject_type: result.subjectData.subject_type,
+      characters: result.characters,
+      srs_stage: result.subjectData.srs_stage,
+      incorrect_reading_answers: incorrectReadingAnswers(result),
+      incorrect_meaning_answers: result.meaning == 'correct' ? 0 : 1,
+      attempts: result.attempts,
+    }))
+  }
+
   function updateStudyResults(updatedResults: ReviewResponse[]) {
     updatedResults.forEach(updatedResult => {
       storeStudyResults.value.forEach((existingResult, index) => {
@@ -181,5 +228,7 @@ export const useStudyStore = defineStore('study', () => {
 
     getSubmittableResults,
     getSubmittableResultsFromFailedSubmissions,
+    getReviewDetails,
+    getPracticeResults,
   }
 })
@@ -22,6 +22,7 @@ export interface ReviewCard {
     readings: Reading[] | undefined,
     meaning_mnemonic: String | undefined,
     reading_mnemonic: String | undefined,
+    srs_stage: SRSLevel,
 }
 
 export interface ReviewTask {
@@ -52,9 +53,26 @@ export interface ReviewResult {
   reading?: 'correct' | 'incorrect' | undefined,
   meaning_attempt: string,
   reading_attempt: string,
+  attempts: ReviewAttempt[],
   subjectData: ReviewCard,
 }
 
+export interface ReviewAttempt {
+  question_type: 'meaning' | 'reading',
+  answer: string,
+  correct: boolean,
+  time_taken_ms: number,
+  answered_at: string,
+}
+
+// Sent with submit_review_batch so the local history has more than WaniKani returns
+export interface ReviewDetails {
+  assignment_id: number,
+  subject_type: SubjectType,
+  characters: String | undefined,
+  attempts: ReviewAttempt[],
+}
+
 export interface WaniKaniResult {
   assignment_id: number,
   incorrect_meaning_answers: number,
@@ -69,6 +87,13 @@ export interface SubmittedReviewData {
   ending_srs_stage: SRSLevel,
   incorrect_meaning_answers: number,
   incorrect_reading_answers: number,
+  // From WaniKani's SRS system, e.g. "Apprentice 3". Missing if it couldn't be fetched.
+  starting_srs_stage_name: string | null,
+  ending_srs_stage_name: string | null,
+  // Worked out from the SRS intervals, null once burned or if the SRS system couldn't be fetched
+  next_review_at: string | null,
+  passed: boolean | null,
+  spaced_repetition_system_id: number | null,
 }
 
 export interface ResultDisplay extends SubmittedReviewData {
@@ -98,9 +123,232 @@ export enum SRSLevelText {
 }
 
 export interface WanipopConfig {
+    config_version: number,
     num_of_reviews_per_batch: number,
     time_between_popups_in_minutes: number,
-    wanikani_api_key: String | null,
+    wanikani_api_key: String | null, // masked, only the last few characters are real
+    secret_store: SecretBackend,
 
     hide_window_decorations: boolean,
+    resume_grace_period_in_seconds: number,
+    popup_behavior: PopupBehavior,
+    batch_policy: BatchPolicy,
+    daily_review_goal: number | null,
+    clear_backlog_by_hour: number | null, // local hour, 0-23
+    goal_catch_up_from_hour: number,
+    goal_catch_up_interval_in_minutes: number,
+    autostart: boolean,
+}
+
+export type SecretBackend = 'keyring' | 'encrypted_file' | 'plain_file'
+
+export type PopupBehavior = 'focus' | 'notify_only'
+
+export type BatchPolicy = 'random' | 'leeches_first'
+
+export interface ApiKeyInfo {
+    username: string,
+    level: number,
+    subscription_type: string,
+    max_level_granted: number,
+    can_submit_reviews: boolean | null,
+    warnings: string[],
+}
+
+export interface ApiKeyError {
+    kind: 'invalid' | 'network' | 'storage',
+    message: string,
+}
+
+// Why the settings in use aren't the ones in the config file
+export interface ConfigWarning {
+    title: string,
+    message: string,
+}
+
+export interface FieldError {
+    field: string,
+    message: string,
+}
+
+export type ConfigUpdateError =
+  | { kind: 'invalid', details: FieldError[] }
+  | { kind: 'storage', details: string }
+
+export interface Profiles {
+    active: string,
+    names: string[],
+}
+
+export interface SettingChange {
+    field: string,
+    current: unknown,
+    imported: unknown,
+}
+
+export interface ImportPreview {
+    bundle_version: number,
+    exported_at: string,
+    changes: SettingChange[],
+    ignored_fields: string[],
+    includes_api_key: boolean,
+    errors: FieldError[],
+}
+
+export interface ImportResult {
+    config: WanipopConfig,
+    warnings: string[],
+}
+
+export interface HistoryEntry {
+  assignment_id: number,
+  subject_id: number,
+  subject_type: SubjectType | null,
+  characters: string | null,
+  starting_srs_stage: SRSLevel,
+  ending_srs_stage: SRSLevel,
+  incorrect_meaning_answers: number,
+  incorrect_reading_answers: number,
+  time_taken_ms: number,
+  created_at: string,
+  attempts: ReviewAttempt[],
+  practice: boolean,
+}
+
+export interface HistoryQuery {
+  since?: string,
+  until?: string,
+  subject_id?: number,
+  subject_type?: SubjectType,
+  subject_types?: SubjectType[],
+  incorrect_only?: boolean,
+  practice?: boolean,
+  limit?: number,
+  offset?: number,
+}
+
+export type StatsRange = 'week' | 'month' | 'quarter' | 'year' | 'all'
+
+export interface Series {
+  labels: string[],
+  values: number[],
+}
+
+export interface Accuracy {
+  correct: number,
+  total: number,
+  percent: number | null,
+}
+
+export interface Breakdown {
+  labels: string[],
+  accuracy: (number | null)[],
+  reviews: number[],
+}
+
+export interface Stats {
+  range: StatsRange,
+  since: string,
+  until: string,
+  total_reviews: number,
+  daily: Series,
+  weekly: Series,
+  overall_accuracy: Accuracy,
+  meaning_accuracy: Accuracy,
+  reading_accuracy: Accuracy,
+  by_subject_type: Breakdown,
+  by_srs_stage: Breakdown,
+  current_streak: number,
+  longest_streak: number,
+}
+
+export interface Leech {
+  subject_id: number,
+  subject_type: SubjectType,
+  characters: string | null,
+  meaning: string | null,
+  score: number,
+  meaning_incorrect: number,
+  reading_incorrect: number,
+  percentage_correct: number,
+}
+
+export type PracticeSource =
+  | { kind: 'recent_mistakes', days?: number }
+  | { kind: 'burned' }
+  | { kind: 'level', level: number }
+
+// Practice answers go to submit_practice_batch, never to WaniKani
+export interface PracticeResult extends WaniKaniResult {
+  subject_id: number,
+  subject_type: SubjectType,
+  characters: String | undefined,
+  srs_stage: SRSLevel,
+  attempts: ReviewAttempt[],
+}
+
+export type ForecastRange = 'day' | 'week'
+
+export interface GroupCounts {
+  apprentice: number,
+  guru: number,
+  master: number,
+  enlightened: number,
+  total: number,
+}
+
+export interface ForecastSlot {
+  starts_at: string,
+  label: string,
+  counts: GroupCounts,
+}
+
+export interface Forecast {
+  range: ForecastRange,
+  available_now: GroupCounts,
+  slots: ForecastSlot[],
+}
+
+export interface LevelTime {
+  level: number,
+  unlocked_at: string | null,
+  passed_at: string | null,
+  duration_in_seconds: number | null,
+  in_progress: boolean,
+}
+
+export interface DayCount {
+  date: string,
+  reviews: number,
+  met: boolean | null,
+}
+
+export interface GoalProgress {
+  date: string,
+  reviews_done: number,
+  review_goal: number | null,
+  reviews_available: number | null,
+  clear_backlog_by: string | null,
+  met: boolean,
+  behind: boolean,
+  catching_up: boolean,
+  recent_days: DayCount[],
+}
+
+// See src-tauri/src/export.rs for the columns of each
+export type ExportData = 'reviews' | 'attempts' | 'daily_stats'
+export type ExportFormat = 'csv' | 'jsonl'
+
+export interface ExportOptions {
+  data?: ExportData,
+  format?: ExportFormat,
+  since?: string, // local date, YYYY-MM-DD, included
+  until?: string,
+  subject_types?: SubjectType[],
+  include_practice?: boolean,
+}
+
+export interface ExportSummary {
+  rows: number,
+  schema_version: number,
 }